@@ -1,14 +1,27 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use regex::Regex;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use crate::module_ref::ModuleRef;
+use crate::module_ref::{ModuleRef, TestFunction};
 
 pub fn discover<P: AsRef<Path>>(path: P) -> Result<Vec<ModuleRef>> {
     let mut source_files = discover_source_files(&path, &PathBuf::new())?;
 
-    let pattern = Regex::new(r"(?sU)#\[cogno_test.*].*fn (?P<fname>.*)\(")?;
+    let pattern = Regex::new(
+        r"(?sU)#\[cogno_test(?P<factory>_factory)?(?:\((?P<attr>.*)\))?\].*fn (?P<fname>.*)\(",
+    )?;
+    let vectors_pattern = Regex::new(r#"vectors\s*=\s*"(?P<path>[^"]*)""#)?;
+    let depends_on_pattern = attr_pattern("depends_on")?;
+    let spec_pattern = attr_pattern("spec")?;
+    let tags_pattern = attr_pattern("tags")?;
+    let name_pattern = Regex::new(r#""(?P<name>[^"]*)""#)?;
+    let assertion_pattern =
+        Regex::new(r#"\b(?:must|should|may)(?:_not)?_eq!\s*\(\s*"(?P<id>[^"]*)""#)?;
+    let serial_pattern = Regex::new(r#"serial\s*=\s*"(?P<group>[^"]*)""#)?;
+
+    // Vector files are relative to the crate root, which is the parent of the source directory
+    let crate_root = path.as_ref().parent().unwrap_or(path.as_ref());
 
     for module_ref in source_files.iter_mut() {
         let mut file = File::open(module_ref.get_path())?;
@@ -16,8 +29,61 @@ pub fn discover<P: AsRef<Path>>(path: P) -> Result<Vec<ModuleRef>> {
         file.read_to_string(&mut string)?;
 
         for captures in pattern.captures_iter(string.as_str()) {
+            let attr = captures.name("attr").map(|a| a.as_str()).unwrap_or("");
+            let attr_values = |pattern: &Regex| -> Vec<String> {
+                pattern
+                    .captures(attr)
+                    .and_then(|c| c.name("value"))
+                    .map(|value| {
+                        name_pattern
+                            .captures_iter(value.as_str())
+                            .map(|c| c["name"].to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+
+            // Dependencies are checked and ordered when the test suite is built
+            let depends_on = attr_values(&depends_on_pattern);
+
+            let serial = captures
+                .name("attr")
+                .and_then(|attr| serial_pattern.captures(attr.as_str()))
+                .map(|c| c["group"].to_string());
+
+            // Assertion ids are listed for planning and review, they are not needed to run the tests
+            let body = function_body(&string[captures.get(0).unwrap().end()..]);
+            let mut assertion_ids: Vec<String> = assertion_pattern
+                .captures_iter(body)
+                .map(|c| c["id"].to_string())
+                .collect();
+            assertion_ids.sort();
+            assertion_ids.dedup();
+
             if let Some(m) = captures.name("fname") {
-                module_ref.add_function(m.as_str().to_string());
+                module_ref.add_function(TestFunction {
+                    name: m.as_str().to_string(),
+                    depends_on,
+                    serial,
+                    spec_ids: attr_values(&spec_pattern),
+                    tags: attr_values(&tags_pattern),
+                    assertion_ids,
+                    factory: captures.name("factory").is_some(),
+                });
+            }
+
+            let vectors = captures
+                .name("attr")
+                .and_then(|attr| vectors_pattern.captures(attr.as_str()))
+                .and_then(|c| c.name("path"));
+            if let Some(vectors) = vectors {
+                if !crate_root.join(vectors.as_str()).is_file() {
+                    return Err(anyhow!(
+                        "test vectors [{}] used in [{}] were not found",
+                        vectors.as_str(),
+                        module_ref.get_path().display()
+                    ));
+                }
             }
         }
     }
@@ -29,6 +95,54 @@ pub fn discover<P: AsRef<Path>>(path: P) -> Result<Vec<ModuleRef>> {
         .collect())
 }
 
+/// Match a key in a test attribute whose value is a string or a list of strings.
+fn attr_pattern(key: &str) -> Result<Regex> {
+    Ok(Regex::new(&format!(
+        r#"\b{}\s*=\s*(?P<value>\[[^\]]*\]|"[^"]*")"#,
+        key
+    ))?)
+}
+
+/// The body of the function which starts the source, without the braces around it.
+///
+/// Braces inside string and character literals are skipped, which is enough for test functions.
+fn function_body(source: &str) -> &str {
+    let start = match source.find('{') {
+        Some(start) => start + 1,
+        None => return "",
+    };
+
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut chars = source[start..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '\'' if !in_string => {
+                // A character literal like '{', rather than a lifetime
+                let rest = &source[start + i + 1..];
+                let mut literal = rest.chars();
+                if let (Some(_), Some('\'')) = (literal.next(), literal.next()) {
+                    chars.nth(1);
+                }
+            }
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return &source[start..start + i];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    &source[start..]
+}
+
 fn discover_source_files<P1: AsRef<Path>>(path: &P1, sub: &PathBuf) -> Result<Vec<ModuleRef>> {
     let search = path.as_ref().join(sub);
 
@@ -47,16 +47,64 @@ fn main() -> Result<()> {
         std::env::set_var("COGNO_REPORTER", reporter);
     }
 
-    let spec_args: Option<ValuesRef<String>> = args.get_many("spec");
-    if let Some(spec_args) = spec_args {
-        let value: String = itertools::intersperse(spec_args.map(|a| a.as_str()), ",").collect();
-        std::env::set_var("COGNO_SPECS", value);
+    set_list_env(&args, "spec", "COGNO_SPECS");
+    set_list_env(&args, "modifier", "COGNO_MODIFIERS");
+    set_list_env(&args, "tag", "COGNO_TAGS");
+    set_list_env(&args, "exclude-tag", "COGNO_EXCLUDE_TAGS");
+
+    let test_arg: Option<&String> = args.get_one("test");
+    if let Some(test) = test_arg {
+        std::env::set_var("COGNO_TEST_FILTER", test);
+    }
+
+    let exact_flag: bool = args.get_flag("exact");
+    if exact_flag {
+        std::env::set_var("COGNO_TEST_EXACT", "true");
+    }
+
+    let retries_arg: Option<&u32> = args.get_one("retries");
+    if let Some(retries) = retries_arg {
+        std::env::set_var("COGNO_RETRIES", retries.to_string());
+    }
+
+    let layer_modifiers_flag: bool = args.get_flag("layer-modifiers");
+    if layer_modifiers_flag {
+        std::env::set_var("COGNO_LAYER_MODIFIERS", "true");
+    }
+
+    let jobs_arg: Option<&u32> = args.get_one("jobs");
+    if let Some(jobs) = jobs_arg {
+        std::env::set_var("COGNO_JOBS", jobs.to_string());
+    }
+
+    let log_level_arg: Option<&String> = args.get_one("log-level");
+    if let Some(log_level) = log_level_arg {
+        std::env::set_var("COGNO_LOG_LEVEL", log_level);
+    }
+
+    let isolate_arg: Option<&String> = args.get_one("isolate");
+    if let Some(isolate) = isolate_arg {
+        std::env::set_var("COGNO_ISOLATE", isolate);
+    }
+
+    let baseline_arg: Option<&String> = args.get_one("baseline");
+    if let Some(baseline) = baseline_arg {
+        std::env::set_var("COGNO_BASELINE", baseline);
+    }
+
+    let backtrace_flag: bool = args.get_flag("backtrace");
+    if backtrace_flag {
+        std::env::set_var("COGNO_BACKTRACE", "true");
     }
 
-    let modifier_args: Option<ValuesRef<String>> = args.get_many("modifier");
-    if let Some(modifier_args) = modifier_args {
-        let value: String = itertools::intersperse(modifier_args.map(|a| a.as_str()), ",").collect();
-        std::env::set_var("COGNO_MODIFIERS", value);
+    let nocapture_flag: bool = args.get_flag("nocapture");
+    if nocapture_flag {
+        std::env::set_var("COGNO_NOCAPTURE", "true");
+    }
+
+    let update_baseline_flag: bool = args.get_flag("update-baseline");
+    if update_baseline_flag {
+        std::env::set_var("COGNO_UPDATE_BASELINE", "true");
     }
 
     let trace_flag: bool = args.get_flag("trace");
@@ -64,6 +112,16 @@ fn main() -> Result<()> {
         std::env::set_var("COGNO_TRACE", "true");
     }
 
+    // The suite binary lists the tests it was built with, or checks the modifiers against them, instead of running them
+    match args.subcommand() {
+        Some(("list", list_args)) => {
+            let format: &String = list_args.get_one("format").unwrap();
+            std::env::set_var("COGNO_LIST", format);
+        }
+        Some(("check-modifiers", _)) => std::env::set_var("COGNO_CHECK_MODIFIERS", "true"),
+        _ => {}
+    }
+
     let run_result = call_cargo_run();
     if run_result.is_err() {
         let e = run_result.unwrap_err();
@@ -76,6 +134,15 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Pass a repeated argument to the test binary as a comma separated environment variable.
+fn set_list_env(args: &ArgMatches, arg: &str, var: &str) {
+    let values: Option<ValuesRef<String>> = args.get_many(arg);
+    if let Some(values) = values {
+        let value: String = itertools::intersperse(values.map(|a| a.as_str()), ",").collect();
+        std::env::set_var(var, value);
+    }
+}
+
 #[derive(Parser)]
 struct CognoCli {
     #[clap(long)]
@@ -108,12 +175,122 @@ fn make_command() -> Command {
                 .action(ArgAction::Append)
                 .value_name("PATH"),
         )
+        .arg(
+            Arg::new("layer-modifiers")
+                .long("layer-modifiers")
+                .help("apply the --modifier files in order, so that later files override earlier ones instead of conflicting")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .help("only run tests with a tag")
+                .action(ArgAction::Append)
+                .value_name("TAG"),
+        )
+        .arg(
+            Arg::new("exclude-tag")
+                .long("exclude-tag")
+                .help("do not run tests with a tag")
+                .action(ArgAction::Append)
+                .value_name("TAG"),
+        )
+        .arg(
+            Arg::new("test")
+                .long("test")
+                .help("only run tests with a name matching a glob, or a regex prefixed with `re:`")
+                .action(ArgAction::Set)
+                .value_name("PATTERN"),
+        )
+        .arg(
+            Arg::new("exact")
+                .long("exact")
+                .help("only run the test named exactly by --test")
+                .action(ArgAction::SetTrue)
+                .requires("test"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .help("retry tests which fail, unless the test sets its own retries")
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(u32))
+                .value_name("N"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("run up to N test functions at the same time")
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(u32).range(1..))
+                .value_name("N"),
+        )
+        .arg(
+            Arg::new("backtrace")
+                .long("backtrace")
+                .help("add a backtrace to the report of tests which panic")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("nocapture")
+                .long("nocapture")
+                .help("let tests write to the console instead of capturing their output")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .help("the most detailed tracing events to add to the report [error|warn|info|debug|trace]")
+                .action(ArgAction::Set)
+                .value_parser(["error", "warn", "info", "debug", "trace"])
+                .value_name("LEVEL"),
+        )
+        .arg(
+            Arg::new("isolate")
+                .long("isolate")
+                .help("run each test, or the tests of each spec, in a separate process [test|spec]")
+                .action(ArgAction::Set)
+                .value_parser(["test", "spec"])
+                .value_name("BY"),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .help("compare results with a baseline of known failures and only fail on regressions")
+                .action(ArgAction::Set)
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("update-baseline")
+                .long("update-baseline")
+                .help("write the failures of this run to the baseline given by --baseline")
+                .action(ArgAction::SetTrue)
+                .requires("baseline"),
+        )
         .arg(
             Arg::new("trace")
                 .long("trace")
                 .help("Enable tracing")
                 .action(ArgAction::SetTrue)
         )
+        .subcommand(
+            Command::new("list")
+                .about("List the tests with their specs, tags, source file and assertion ids, without running them")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("print the list as a table or as JSON [table|json]")
+                        .action(ArgAction::Set)
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .value_name("FORMAT"),
+                ),
+        )
+        .subcommand(
+            Command::new("check-modifiers")
+                .about("Check that the ids in the --modifier files match the tests, and that the modifiers do not conflict"),
+        )
         // Taken from Cargo's `src/bin/cargo/commands/run.rs`
         .about("Run a binary or example of the local package")
         .arg_quiet()
@@ -128,7 +305,6 @@ fn make_command() -> Command {
             "Name of the example target to run",
         )
         .arg_package("Package with the target to run")
-        .arg_jobs()
         .arg_release("Build artifacts in release mode, with optimizations")
         .arg_profile("Build artifacts with the specified profile")
         .arg_features()
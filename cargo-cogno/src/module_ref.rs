@@ -5,7 +5,20 @@ use serde::Serialize;
 pub struct ModuleRef {
     path: PathBuf,
     relative_path: PathBuf,
-    test_functions: Vec<String>,
+    test_functions: Vec<TestFunction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestFunction {
+    pub name: String,
+    pub depends_on: Vec<String>,
+    pub serial: Option<String>,
+    pub spec_ids: Vec<String>,
+    pub tags: Vec<String>,
+    /// The ids of the assertions made in the body of the function.
+    pub assertion_ids: Vec<String>,
+    /// Whether this is a test factory, whose tests are only known at runtime.
+    pub factory: bool,
 }
 
 impl ModuleRef {
@@ -21,7 +34,7 @@ impl ModuleRef {
         &self.path
     }
 
-    pub fn add_function(&mut self, f: String) {
+    pub fn add_function(&mut self, f: TestFunction) {
         self.test_functions.push(f);
     }
 
@@ -1,12 +1,31 @@
 use debug::debug_enabled;
-use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
 use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::path::Path;
+use test_attr::{TestArg, TestAttr};
 
 mod debug;
 mod module_ref;
+mod ordering;
+mod test_attr;
+
+/// Macros which need access to the controller, it is passed to them as their first argument.
+const CONTROLLER_MACROS: [&str; 9] = [
+    "should_eq",
+    "should_not_eq",
+    "must_eq",
+    "must_not_eq",
+    "may_eq",
+    "context_set",
+    "context_get",
+    "step",
+    "subtest",
+];
+
+/// Stands in for the body of a test in generated code until the body is spliced in by [`splice_body`].
+const BODY_PLACEHOLDER: &str = "__cogno_test_body";
 
 /// Mark a function as a Cogno test.
 ///
@@ -18,6 +37,14 @@ mod module_ref;
 /// fn example_test() {}
 /// ```
 ///
+/// A requirement which is restated in a later specification can be tested once for all of them. The test runs
+/// when any of the specifications are enabled.
+///
+/// ```
+/// #[cogno_test(spec = ["rfc-1034", "rfc-2181"])]
+/// fn example_test() {}
+/// ```
+///
 /// If you choose not to group your tests by specification because what you are testing is standalone then
 ///
 /// ```
@@ -25,6 +52,67 @@ mod module_ref;
 /// fn example_test() {}
 /// ```
 ///
+/// Tests can be tagged to group them by something other than their specification, such as the role of the program
+/// under test or the cost of running the test. Tags are used to select which tests to run.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-1034", tags = ["resolver", "tcp"])]
+/// fn example_test() {}
+/// ```
+///
+/// A test can be driven by a file of test vectors using the `vectors` parameter. The path is relative to the
+/// root of your crate and the file is embedded at compile time. Each vector is deserialised into the type of
+/// the test function's argument and the test runs, and is reported, once per vector.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-4648", vectors = "vectors/rfc4648.toml")]
+/// fn example_test(vector: Base64Vector) {}
+/// ```
+///
+/// A test can depend on other test functions with the `depends_on` parameter. The test is skipped unless every test
+/// declared by those functions passed, and tests are run in an order which puts dependencies first.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-5936", depends_on = ["soa_query"])]
+/// fn zone_transfer() {}
+/// ```
+///
+/// A known non-conformance of the program under test can be marked as an expected failure with a reason, such as
+/// a link to the issue tracking it. The test is reported as an expected failure, or as an unexpected pass once the
+/// problem is fixed.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-1034", expect_fail = "wildcards are not supported yet, see #42")]
+/// fn example_test() {}
+/// ```
+///
+/// A test which can fail for reasons outside the program under test, such as one which uses the network, can be
+/// retried. A test which passes after being retried is reported as flaky.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-1034", retries = 2)]
+/// fn example_test() {}
+/// ```
+///
+/// When tests run in parallel, tests which share a resource can be put in the same `serial` group so that only one
+/// of them runs at a time.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-1034", serial = "port-53")]
+/// fn example_test() {}
+/// ```
+///
+/// A test can return a `Result<(), E>` where `E` implements `Display`, so that setup which can fail can use `?`.
+/// An `Err` is reported as the error of the test rather than as a panic.
+///
+/// ```
+/// #[cogno_test(spec = "rfc-1034")]
+/// fn example_test() -> Result<(), std::io::Error> {
+///     let zone = std::fs::read_to_string("example.zone")?;
+///     Ok(())
+/// }
+/// ```
+///
 /// Your test should use the provided assertion macros like `should_eq!` and avoid panicking unless necessary.
 /// That means you should avoid Rust's `assert_eq!` and other test assertion macros.
 /// However, a program failing to start or being unable to open a file would be valid reasons to panic and fail the test.
@@ -35,177 +123,164 @@ pub fn cogno_test(attr: TokenStream, item: TokenStream) -> TokenStream {
         println!("cogno_test => {}", item.to_string());
     }
 
-    let mut spec_id = String::new();
-    let mut header_src = String::new();
-    let mut attr_iter = attr.into_iter();
-    if let Some(TokenTree::Ident(id)) = attr_iter.next() {
-        match id.to_string().as_str() {
-            "spec" => {
-                attr_iter.next();
-                if let Some(TokenTree::Literal(id)) = attr_iter.next() {
-                    spec_id = id.to_string();
-                    header_src.push_str(
-                        format!(
-                            r#"
-                        if !controller.lock().unwrap().is_spec_enabled({}) {{
-                            cogno::tracing::event!(cogno::tracing::Level::INFO, "skipped");
-                            return;
-                        }}
-                        "#,
-                            spec_id
-                        )
-                            .as_str(),
-                    );
-                }
-            }
-            _ => {
-                panic!("Unrecognised syntax in test attribute");
-            }
-        }
-    }
-
-    // Default to literal empty string if no spec_id provided.
-    // This is expected to be unusual, but is supported
-    if spec_id.is_empty() {
-        spec_id.push_str("\"\"");
-    }
+    let test_attr = TestAttr::parse(attr);
+
+    // A test without any spec_ids is expected to be unusual, but is supported
+    let spec_ids = format!("&[{}]", test_attr.spec_ids.join(", "));
+    let tags = format!("&[{}]", test_attr.tags.join(", "));
+    let depends_on = format!(
+        "vec![{}]",
+        test_attr
+            .depends_on
+            .iter()
+            .map(|d| format!("{}.to_string()", d))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
-    let mut ret = TokenStream::new();
+    let test_fn = TestFn::parse(item);
+    let fn_name = &test_fn.name;
+    let new_body = inject_controller(test_fn.body.clone());
+
+    let expect_fail = match &test_attr.expect_fail {
+        Some(reason) => format!("Some({}.to_string())", reason),
+        None => "None".to_string(),
+    };
+
+    let retries = match &test_attr.retries {
+        Some(retries) => format!("Some({})", retries),
+        None => "None".to_string(),
+    };
+
+    let test_meta = |name: &str| {
+        format!(
+            r#"cogno::TestMeta {{
+            function: "{}".to_string(),
+            depends_on: {},
+            expect_fail: {},
+            retries: {},
+            ..cogno::TestMeta::new({}, {}, {})
+        }}"#,
+            fn_name, depends_on, expect_fail, retries, name, spec_ids, tags
+        )
+    };
+
+    let wrapped_body = match &test_attr.vectors {
+        Some(vectors) => {
+            let test_arg = TestArg::parse(test_fn.args.clone())
+                .expect("a test with vectors must take the vector as its only argument");
 
-    let mut fn_found = false;
-    let mut param_injected = false;
-    let mut fn_name = String::new();
-    for token in item {
-        if !param_injected {
-            if !fn_found {
-                if token.to_string() == "fn" {
-                    fn_found = true;
-                }
-                ret.extend(Some(token));
-                continue;
-            }
+            format!(
+                r#"
+    let controller_thread_ref = &*controller;
 
-            match token {
-                TokenTree::Group(g) => {
-                    if g.delimiter() == Delimiter::Parenthesis {
-                        ret.extend(to_token_stream("(controller: &mut std::sync::Arc<std::sync::Mutex<cogno::TestController>>)"));
-                        param_injected = true;
-                    } else {
-                        panic!("unexpected group after test function name");
-                    }
-                }
-                TokenTree::Ident(i) => {
-                    fn_name = i.to_string();
-                    ret.extend(Some(TokenTree::Ident(i)));
-                }
-                other => {
-                    ret.extend(Some(other));
-                }
-            }
-            continue;
+    let load_vectors = || cogno::parse_vectors::<{}>(
+        {},
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", {}))
+    );
+    let vectors = match load_vectors() {{
+        Ok(vectors) => vectors,
+        Err(e) => {{
+            cogno::run_test(controller, {}, move || -> () {{
+                panic!("could not load test vectors - {{}}", e);
+            }});
+            return;
+        }}
+    }};
+
+    for (vector_index, vector) in vectors.into_iter().enumerate() {{
+        let meta = {};
+        // The vector is moved into the first attempt, a retry loads it again
+        let vector = std::sync::Mutex::new(Some(vector));
+        cogno::run_test(controller, meta, {} {{
+            let vector = vector.lock().unwrap().take();
+            let {}: {} = vector.unwrap_or_else(|| load_vectors().unwrap().swap_remove(vector_index));
+            {}
+        }});
+    }}
+    "#,
+                test_arg.arg_type,
+                vectors,
+                vectors,
+                test_meta(&format!("\"{}\"", fn_name)),
+                test_meta(&format!("&format!(\"{}[{{}}]\", vector_index)", fn_name)),
+                test_fn.closure_head(),
+                test_arg.pattern,
+                test_arg.arg_type,
+                BODY_PLACEHOLDER
+            )
         }
+        None => format!(
+            r#"
+    let controller_thread_ref = &*controller;
 
-        let group = match token {
-            TokenTree::Group(g) => {
-                if g.delimiter() == Delimiter::Brace {
-                    g
-                } else {
-                    ret.extend(Some(TokenTree::Group(g)));
-                    continue;
-                }
-            }
-            other => {
-                ret.extend(Some(other));
-                continue;
-            }
-        };
+    cogno::run_test(controller, {}, {} {{
+        {}
+    }});
+    "#,
+            test_meta(&format!("\"{}\"", fn_name)),
+            test_fn.closure_head(),
+            BODY_PLACEHOLDER
+        ),
+    };
 
-        let mut new_body = TokenStream::new();
-
-        let mut group_stream = group.stream().into_iter().peekable();
-        while let Some(tt) = group_stream.peek() {
-            match tt.to_string().as_str() {
-                "should_eq" | "should_not_eq" | "must_eq" | "must_not_eq" | "may_eq" => {
-                    new_body.extend(group_stream.next());
-
-                    if group_stream.peek().is_some()
-                        && group_stream.peek().unwrap().to_string() == "!"
-                    {
-                        new_body.extend(group_stream.next());
-                        match group_stream.next() {
-                            Some(TokenTree::Group(g)) => {
-                                let mut new_group = TokenStream::new();
-                                new_group.extend(to_token_stream("controller_thread_ref,"));
-                                new_group.extend(g.stream());
-
-                                new_body.extend(Some(TokenTree::from(Group::new(
-                                    g.delimiter(),
-                                    new_group,
-                                ))));
-                            }
-                            _ => {
-                                panic!("expected arguments after assertion macro");
-                            }
-                        }
-                    } else {
-                        panic!("identifier conflicts with an assertion macro")
-                    }
-                }
-                _ => {
-                    new_body.extend(group_stream.next());
-                }
-            }
-        }
+    let ret = test_fn.to_token_stream(splice_body(to_token_stream(wrapped_body.as_str()), &new_body));
 
-        let mut traced_header_src = String::new();
-        traced_header_src.push_str(format!(r#"
-        let span = cogno::tracing::span!(cogno::tracing::Level::INFO, "{}");
-        let _enter = span.enter();
-        cogno::tracing::event!(cogno::tracing::Level::INFO, "enter");
-        {}
-        "#, fn_name, header_src).as_str());
+    if debug_enabled() {
+        println!("cogno_test transformed => {}", ret.to_string());
+    }
 
-        let wrapped_body = to_token_stream(
-            format!(
-                r#"
-            {}
-            controller.lock().unwrap().register("{}", {});
-
-    let controller_thread_ref = controller.clone();
-
-    let result = std::thread::Builder::new()
-    .name("{}".to_string())
-    .spawn(move || {{
-        std::panic::catch_unwind(move || {{
-                {}
-            }})
-        }}).unwrap().join().unwrap();
-
-        cogno::tracing::event!(cogno::tracing::Level::INFO, "exit");
-        match result {{
-            Ok(_) => {{
-                controller.lock().unwrap().complete();
-            }}
-            _ => {{}}
-        }};
-        "#,
-                traced_header_src,
-                fn_name,
-                spec_id,
-                fn_name,
-                new_body.to_string()
-            )
-                .as_str(),
-        );
+    ret
+}
 
-        ret.extend(Some(TokenTree::from(Group::new(
-            group.delimiter(),
-            wrapped_body,
-        ))));
+/// Mark a function as a factory for tests which are only known at runtime.
+///
+/// The function takes a single `&mut TestFactory` argument which is used to register tests. Each test is given
+/// a name, its spec identifiers and a closure containing the test body.
+///
+/// ```
+/// #[cogno_test_factory]
+/// fn example_factory(factory: &mut TestFactory) {
+///     for name in ["first", "second"] {
+///         factory.register(name, &["rfc-1034"], || {
+///             should_eq!("example_assertion_id", 'a', 'a');
+///         });
+///     }
+/// }
+/// ```
+///
+/// The registered tests run in the same way as tests marked with `cogno_test`. Assertions must only be used inside
+/// the closures passed to `register`.
+#[proc_macro_attribute]
+pub fn cogno_test_factory(_: TokenStream, item: TokenStream) -> TokenStream {
+    if debug_enabled() {
+        println!("cogno_test_factory => {}", item.to_string());
     }
 
+    let test_fn = TestFn::parse(item);
+    let factory_arg = TestArg::parse(test_fn.args.clone())
+        .expect("a test factory must take a `&mut TestFactory` as its only argument");
+
+    let wrapped_body = format!(
+        r#"
+    let controller_thread_ref = &*controller;
+
+    cogno::run_factory(&*controller, "{}", |test_factory| {{
+        let {}: {} = test_factory;
+        {}
+    }});
+    "#,
+        test_fn.name,
+        factory_arg.pattern,
+        factory_arg.arg_type,
+        BODY_PLACEHOLDER
+    );
+
+    let new_body = inject_controller(test_fn.body.clone());
+    let ret = test_fn.to_token_stream(splice_body(to_token_stream(wrapped_body.as_str()), &new_body));
+
     if debug_enabled() {
-        println!("cogno_test transformed => {}", ret.to_string());
+        println!("cogno_test_factory transformed => {}", ret.to_string());
     }
 
     ret
@@ -242,9 +317,37 @@ pub fn cogno_main(_: TokenStream, item: TokenStream) -> TokenStream {
     let mut ret = String::new();
     ret.push_str("fn main() {");
 
+    // Listing the tests and checking modifiers need nothing from the test run, so they happen before anything is set up
+    ret.push_str(
+        format!(
+            r#"
+    let inventory = cogno::Inventory::parse(include_str!({:?})).unwrap();
+    if let Some(list_format) = cogno::list_format().unwrap() {{
+        cogno::print_listing(&inventory, list_format).unwrap();
+        return;
+    }}
+    if let Some(exit_code) = cogno::check_modifiers(&inventory).unwrap() {{
+        std::process::exit(exit_code);
+    }}
+    "#,
+            manifest_path.unwrap()
+        )
+        .as_str(),
+    );
+
     ret.push_str(r#"
-    if "true" == std::env::var("COGNO_TRACE").unwrap_or(String::from("false")).as_str() {
-        let sub = cogno::tracing_subscriber::FmtSubscriber::new();
+    {
+        use cogno::tracing_subscriber::layer::{Layer, SubscriberExt};
+
+        // Events inside tests are always collected for the report, tracing the harness is optional
+        let trace = "true" == std::env::var("COGNO_TRACE").unwrap_or(String::from("false")).as_str();
+        let fmt_layer = trace.then(|| {
+            cogno::tracing_subscriber::fmt::layer()
+                .with_filter(cogno::tracing_subscriber::filter::LevelFilter::INFO)
+        });
+        let sub = cogno::tracing_subscriber::registry()
+            .with(cogno::TestLogLayer::new())
+            .with(fmt_layer);
         cogno::tracing::subscriber::set_global_default(sub)
             .expect("setting tracing default failed");
     }
@@ -252,29 +355,64 @@ pub fn cogno_main(_: TokenStream, item: TokenStream) -> TokenStream {
     let _enter = span.enter();
     cogno::tracing::event!(cogno::tracing::Level::INFO, "starting");
 
-    let mut controller = std::sync::Arc::new(std::sync::Mutex::new(cogno::TestController::new().unwrap()));
+    let controller = std::sync::Arc::new(std::sync::Mutex::new(cogno::TestController::new().unwrap()));
     "#);
 
     ret.push_str(
         r#"
     let controller_panic_ref = controller.clone();
+    // Panics outside of a test, such as in the harness, are not part of the report and are shown as usual
+    let previous_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
-        cogno::tracing::event!(cogno::tracing::Level::INFO, "captured a panic - {}", info);
-        let mut controller_handle = controller_panic_ref.lock().unwrap();
-        controller_handle.set_panic_info(info.to_string());
+        // Tests stopped on purpose are handled by the test runner
+        if info.payload().is::<cogno::TestAbort>() {
+            return;
+        }
+
+        cogno::tracing::event!(target: "cogno", cogno::tracing::Level::INFO, "captured a panic - {}", info);
+        let recorded = controller_panic_ref.lock().unwrap().set_panic_info(cogno::PanicDef::capture(info));
+        if !recorded {
+            previous_hook(info);
+        }
     }));
     "#,
     );
 
-    for module_ref in manifest {
-        ret.push_str(format!("{}", module_ref.to_source()).as_str());
+    // Tests start in dependency order, which is checked here so that mistakes fail the build
+    let test_calls = ordering::order_test_calls(&manifest).unwrap_or_else(|e| panic!("{}", e));
+    ret.push_str("let test_functions = [");
+    for (call_path, test_function) in test_calls {
+        let depends_on: Vec<String> = test_function
+            .depends_on
+            .iter()
+            .map(|d| format!("{:?}", d))
+            .collect();
+        let serial = match &test_function.serial {
+            Some(group) => format!("Some({:?})", group),
+            None => "None".to_string(),
+        };
+        ret.push_str(
+            format!(
+                "cogno::TestFunction {{ name: {:?}, depends_on: &[{}], serial: {}, run: {} }},\n",
+                test_function.name,
+                depends_on.join(", "),
+                serial,
+                call_path
+            )
+            .as_str(),
+        );
     }
+    ret.push_str("];\ncogno::run_test_functions(&controller, &test_functions);\n");
 
     ret.push_str(r#"
     cogno::tracing::event!(cogno::tracing::Level::INFO, "finishing report");
     let finalize_result = controller.lock().unwrap().finalize();
-    finalize_result.unwrap();
+    let succeeded = finalize_result.unwrap();
     cogno::tracing::event!(cogno::tracing::Level::INFO, "done");
+
+    if !succeeded {
+        std::process::exit(1);
+    }
     "#);
     ret.push_str("}");
 
@@ -287,6 +425,179 @@ pub fn cogno_main(_: TokenStream, item: TokenStream) -> TokenStream {
     ret
 }
 
+/// A function split into the parts that the Cogno attributes rewrite.
+struct TestFn {
+    head: TokenStream,
+    name: String,
+    args: TokenStream,
+    tail: TokenStream,
+    /// The declared return type, which moves from the function to the closure that runs the test.
+    return_type: Option<String>,
+    body: TokenStream,
+}
+
+impl TestFn {
+    fn parse(item: TokenStream) -> Self {
+        let mut test_fn = TestFn {
+            head: TokenStream::new(),
+            name: String::new(),
+            args: TokenStream::new(),
+            tail: TokenStream::new(),
+            return_type: None,
+            body: TokenStream::new(),
+        };
+
+        let mut fn_found = false;
+        let mut args_found = false;
+        for token in item {
+            if !args_found {
+                if !fn_found {
+                    if token.to_string() == "fn" {
+                        fn_found = true;
+                    }
+                    test_fn.head.extend(Some(token));
+                    continue;
+                }
+
+                match token {
+                    TokenTree::Group(g) => {
+                        if g.delimiter() == Delimiter::Parenthesis {
+                            test_fn.args = g.stream();
+                            args_found = true;
+                        } else {
+                            panic!("unexpected group after test function name");
+                        }
+                    }
+                    TokenTree::Ident(i) => {
+                        test_fn.name = i.to_string();
+                        test_fn.head.extend(Some(TokenTree::Ident(i)));
+                    }
+                    other => {
+                        test_fn.head.extend(Some(other));
+                    }
+                }
+                continue;
+            }
+
+            match token {
+                TokenTree::Group(g) if g.delimiter() == Delimiter::Brace => {
+                    test_fn.body = g.stream();
+                }
+                other => {
+                    test_fn.tail.extend(Some(other));
+                }
+            }
+        }
+
+        test_fn.split_return_type();
+        test_fn
+    }
+
+    /// Move a `-> Type` out of the tail, the generated function always returns `()`.
+    fn split_return_type(&mut self) {
+        let tail: Vec<TokenTree> = self.tail.clone().into_iter().collect();
+        let arrow = tail.windows(2).position(|pair| match pair {
+            [TokenTree::Punct(a), TokenTree::Punct(b)] => a.as_char() == '-' && b.as_char() == '>',
+            _ => false,
+        });
+
+        if let Some(arrow) = arrow {
+            self.return_type = Some(tail[arrow + 2..].iter().cloned().collect::<TokenStream>().to_string());
+            self.tail = tail[..arrow].iter().cloned().collect();
+        }
+    }
+
+    /// The closure header for the test body, keeping the declared return type.
+    fn closure_head(&self) -> String {
+        match &self.return_type {
+            Some(return_type) => format!("move || -> {}", return_type),
+            // Explicit so that a body which only panics is not inferred to return `!`
+            None => "move || -> ()".to_string(),
+        }
+    }
+
+    /// Rebuild the function so that it accepts the controller, with a new body.
+    fn to_token_stream(&self, body: TokenStream) -> TokenStream {
+        let mut ret = self.head.clone();
+        ret.extend(to_token_stream(
+            "(controller: &mut std::sync::Arc<std::sync::Mutex<cogno::TestController>>)",
+        ));
+        ret.extend(self.tail.clone());
+        ret.extend(Some(TokenTree::from(Group::new(Delimiter::Brace, body))));
+        ret
+    }
+}
+
+/// Pass the controller to every use of a Cogno macro, including those in nested blocks. Other uses of the same
+/// names, such as a variable called `step`, are left as they are.
+fn inject_controller(stream: TokenStream) -> TokenStream {
+    let mut new_body = TokenStream::new();
+
+    let mut stream = stream.into_iter().peekable();
+    while let Some(tt) = stream.next() {
+        match tt {
+            TokenTree::Ident(i) if CONTROLLER_MACROS.contains(&i.to_string().as_str()) => {
+                new_body.extend(Some(TokenTree::Ident(i)));
+
+                // A lone `!` makes this a macro call, rather than a comparison such as `step != 2`
+                let is_macro_call = matches!(
+                    stream.peek(),
+                    Some(TokenTree::Punct(p)) if p.as_char() == '!' && p.spacing() == Spacing::Alone
+                );
+                if is_macro_call {
+                    new_body.extend(stream.next());
+                    match stream.next() {
+                        Some(TokenTree::Group(g)) => {
+                            let mut new_group = TokenStream::new();
+                            new_group.extend(to_token_stream("controller_thread_ref,"));
+                            new_group.extend(inject_controller(g.stream()));
+
+                            let mut new_group = Group::new(g.delimiter(), new_group);
+                            new_group.set_span(g.span());
+                            new_body.extend(Some(TokenTree::from(new_group)));
+                        }
+                        _ => {
+                            panic!("expected arguments after assertion macro");
+                        }
+                    }
+                }
+            }
+            TokenTree::Group(g) => {
+                let mut new_group = Group::new(g.delimiter(), inject_controller(g.stream()));
+                new_group.set_span(g.span());
+                new_body.extend(Some(TokenTree::from(new_group)));
+            }
+            other => {
+                new_body.extend(Some(other));
+            }
+        }
+    }
+
+    new_body
+}
+
+/// Put the body of a test in place of [`BODY_PLACEHOLDER`] in generated code. The body is kept as tokens, rather
+/// than formatted into the code, so that panics and compile errors point at the source of the test.
+fn splice_body(code: TokenStream, body: &TokenStream) -> TokenStream {
+    let mut ret = TokenStream::new();
+    for tt in code {
+        match tt {
+            TokenTree::Ident(i) if i.to_string() == BODY_PLACEHOLDER => {
+                ret.extend(body.clone());
+            }
+            TokenTree::Group(g) => {
+                let mut new_group = Group::new(g.delimiter(), splice_body(g.stream(), body));
+                new_group.set_span(g.span());
+                ret.extend(Some(TokenTree::from(new_group)));
+            }
+            other => {
+                ret.extend(Some(other));
+            }
+        }
+    }
+    ret
+}
+
 fn to_token_stream(code: &str) -> TokenStream {
     code.parse().unwrap()
 }
@@ -4,11 +4,25 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct ModuleRef {
     relative_path: PathBuf,
-    test_functions: Vec<String>,
+    test_functions: Vec<TestFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestFunction {
+    pub name: String,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub serial: Option<String>,
 }
 
 impl ModuleRef {
-    pub fn to_source(&self) -> String {
+    pub fn test_functions(&self) -> &[TestFunction] {
+        &self.test_functions
+    }
+
+    /// The path to call a test function in this module from the crate root.
+    pub fn call_path(&self, function: &TestFunction) -> String {
         let parent =
             if self.relative_path.ends_with("main.rs") || self.relative_path.ends_with("lib.rs") {
                 let parent = self.relative_path.parent();
@@ -30,11 +44,6 @@ impl ModuleRef {
             parent_import_path.push_str("::");
         }
 
-        let mut out = String::new();
-        for f in &self.test_functions {
-            out.push_str(format!("{}{}(&mut controller);\n", parent_import_path, f).as_str());
-        }
-
-        out
+        format!("{}{}", parent_import_path, function.name)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,52 @@
+use crate::module_ref::{ModuleRef, TestFunction};
+use std::collections::HashSet;
+
+/// Order the test functions so that every test runs after the tests it depends on.
+///
+/// Tests keep the order of the manifest unless a dependency must run first. Dependencies on a test which does
+/// not exist, and dependency cycles, are errors.
+/// Each function is returned with the path to call it from the crate root.
+pub fn order_test_calls(manifest: &[ModuleRef]) -> Result<Vec<(String, &TestFunction)>, String> {
+    let mut pending: Vec<(&ModuleRef, &TestFunction)> = manifest
+        .iter()
+        .flat_map(|m| m.test_functions().iter().map(move |f| (m, f)))
+        .collect();
+
+    let names: HashSet<&str> = pending.iter().map(|(_, f)| f.name.as_str()).collect();
+    for (_, f) in &pending {
+        if let Some(unknown) = f.depends_on.iter().find(|d| !names.contains(d.as_str())) {
+            return Err(format!(
+                "test [{}] depends on [{}] which is not a test",
+                f.name, unknown
+            ));
+        }
+    }
+
+    let mut ordered = Vec::new();
+    let mut done: HashSet<&str> = HashSet::new();
+    while !pending.is_empty() {
+        // A function name can be declared in more than one module, all of them must run first
+        let is_ready = |f: &TestFunction| {
+            f.depends_on.iter().all(|d| {
+                done.contains(d.as_str()) && !pending.iter().any(|(_, p)| &p.name == d)
+            })
+        };
+
+        let next = match pending.iter().position(|(_, f)| is_ready(f)) {
+            Some(next) => next,
+            None => {
+                let cycle: Vec<&str> = pending.iter().map(|(_, f)| f.name.as_str()).collect();
+                return Err(format!(
+                    "dependency cycle between tests [{}]",
+                    cycle.join(", ")
+                ));
+            }
+        };
+
+        let (module_ref, f) = pending.remove(next);
+        ordered.push((module_ref.call_path(f), f));
+        done.insert(f.name.as_str());
+    }
+
+    Ok(ordered)
+}
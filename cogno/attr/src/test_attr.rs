@@ -0,0 +1,141 @@
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// The parsed arguments of a `cogno_test` attribute.
+///
+/// Values are kept as Rust source so that they can be pasted into the generated code.
+#[derive(Debug, Default)]
+pub struct TestAttr {
+    pub spec_ids: Vec<String>,
+    pub tags: Vec<String>,
+    pub vectors: Option<String>,
+    pub depends_on: Vec<String>,
+    pub expect_fail: Option<String>,
+    pub retries: Option<String>,
+}
+
+/// A value in an attribute, either a literal like `"1034"` or a list like `["1034", "2181"]`.
+#[derive(Debug)]
+enum AttrValue {
+    Literal(String),
+    List(Vec<String>),
+}
+
+impl AttrValue {
+    fn into_literal(self, key: &str) -> String {
+        match self {
+            AttrValue::Literal(l) => l,
+            AttrValue::List(_) => panic!("Expected a single value for `{}` in test attribute", key),
+        }
+    }
+
+    fn into_list(self) -> Vec<String> {
+        match self {
+            AttrValue::Literal(l) => vec![l],
+            AttrValue::List(l) => l,
+        }
+    }
+}
+
+impl TestAttr {
+    pub fn parse(attr: TokenStream) -> Self {
+        let mut test_attr = TestAttr::default();
+
+        let mut attr_iter = attr.into_iter().peekable();
+        while let Some(token) = attr_iter.next() {
+            let key = match token {
+                TokenTree::Ident(id) => id.to_string(),
+                _ => panic!("Unrecognised syntax in test attribute"),
+            };
+
+            match attr_iter.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == '=' => {}
+                _ => panic!("Expected `=` after `{}` in test attribute", key),
+            }
+
+            let value = match attr_iter.next() {
+                Some(TokenTree::Literal(l)) => AttrValue::Literal(l.to_string()),
+                Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Bracket => {
+                    AttrValue::List(parse_list(g.stream(), &key))
+                }
+                _ => panic!("Expected a literal value for `{}` in test attribute", key),
+            };
+
+            match key.as_str() {
+                "spec" => test_attr.spec_ids = value.into_list(),
+                "tags" => test_attr.tags = value.into_list(),
+                "vectors" => test_attr.vectors = Some(value.into_literal(&key)),
+                "depends_on" => test_attr.depends_on = value.into_list(),
+                "expect_fail" => test_attr.expect_fail = Some(value.into_literal(&key)),
+                "retries" => test_attr.retries = Some(value.into_literal(&key)),
+                // Scheduling is decided by `cogno_main`, which reads the group from the manifest
+                "serial" => {
+                    value.into_literal(&key);
+                }
+                _ => panic!("Unrecognised syntax in test attribute"),
+            }
+
+            if let Some(TokenTree::Punct(p)) = attr_iter.peek() {
+                if p.as_char() == ',' {
+                    attr_iter.next();
+                }
+            }
+        }
+
+        test_attr
+    }
+}
+
+fn parse_list(list: TokenStream, key: &str) -> Vec<String> {
+    list.into_iter()
+        .filter_map(|token| match token {
+            TokenTree::Literal(l) => Some(l.to_string()),
+            TokenTree::Punct(p) if p.as_char() == ',' => None,
+            _ => panic!("Expected a list of literals for `{}` in test attribute", key),
+        })
+        .collect()
+}
+
+/// A single `pattern: Type` function argument, split at the top level `:`.
+#[derive(Debug)]
+pub struct TestArg {
+    pub pattern: String,
+    pub arg_type: String,
+}
+
+impl TestArg {
+    pub fn parse(args: TokenStream) -> Option<Self> {
+        let mut pattern = TokenStream::new();
+        let mut arg_type = TokenStream::new();
+
+        let mut in_type = false;
+        let mut previous_was_colon = false;
+        for token in args {
+            if in_type {
+                arg_type.extend(Some(token));
+                continue;
+            }
+
+            // A lone `:` separates the pattern from the type, `::` belongs to a path
+            if let TokenTree::Punct(p) = &token {
+                if p.as_char() == ':' && p.spacing() == Spacing::Alone && !previous_was_colon {
+                    in_type = true;
+                    continue;
+                }
+                previous_was_colon = p.as_char() == ':';
+            } else {
+                previous_was_colon = false;
+            }
+
+            pattern.extend(Some(token));
+        }
+
+        if !in_type || pattern.is_empty() || arg_type.is_empty() {
+            return None;
+        }
+
+        Some(TestArg {
+            pattern: pattern.to_string(),
+            arg_type: arg_type.to_string(),
+        })
+    }
+}
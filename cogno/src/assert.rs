@@ -122,3 +122,135 @@ macro_rules! may_eq {
         assert_result.unwrap();
     }};
 }
+
+/// Stop the test and report it as skipped, because a precondition for running it is not met.
+///
+/// The macro accepts the reason the test could not run
+///
+/// ```
+/// skip!("the implementation does not support TCP");
+/// ```
+///
+/// Use this when the test does not apply to the program under test, rather than when it fails.
+#[macro_export]
+macro_rules! skip {
+    ( $reason:expr ) => {{
+        std::panic::panic_any(cogno::TestAbort::Skip($reason.to_string()))
+    }};
+}
+
+/// Stop the test and report it as inconclusive, unless a condition holds.
+///
+/// The macro accepts a condition and the reason the test cannot be evaluated without it
+///
+/// ```
+/// require!(ipv6_loopback_available(), "IPv6 loopback is not available");
+/// ```
+///
+/// Use this when the environment prevents the test from deciding whether the requirement is met, such as
+/// a port being in use. An inconclusive test is neither a pass nor a fail. If an assertion has already
+/// failed then the test is reported as failed.
+#[macro_export]
+macro_rules! require {
+    ( $condition:expr, $reason:expr ) => {{
+        if !$condition {
+            std::panic::panic_any(cogno::TestAbort::Inconclusive($reason.to_string()))
+        }
+    }};
+}
+
+/// Store a value which later tests in the same run can read with `context_get!`.
+///
+/// The macro accepts a [`ContextKey`](crate::ContextKey) and the value to store
+///
+/// ```
+/// const SESSION_TICKET: ContextKey<Vec<u8>> = ContextKey::new("session_ticket");
+///
+/// context_set!(SESSION_TICKET, response.session_ticket.clone());
+/// ```
+///
+/// A value which is already stored for the key is replaced.
+#[macro_export]
+macro_rules! context_set {
+    ( $controller_thread_ref:ident, $key:expr, $value:expr ) => {{
+        let value = $value;
+        $controller_thread_ref
+            .lock()
+            .unwrap()
+            .context_set(&$key, value);
+    }};
+}
+
+/// Read a copy of a value stored by an earlier test with `context_set!`.
+///
+/// The macro accepts a [`ContextKey`](crate::ContextKey) and evaluates to an `Option` which is `None` if no value
+/// has been stored
+///
+/// ```
+/// let session_ticket = context_get!(SESSION_TICKET);
+/// ```
+///
+/// Use `depends_on` so that the test which stores the value runs first. Values are not shared between the processes
+/// of isolated tests, so when the value was not stored in the same process the test is skipped.
+#[macro_export]
+macro_rules! context_get {
+    ( $controller_thread_ref:ident, $key:expr ) => {{
+        let value = $controller_thread_ref.lock().unwrap().context_get(&$key);
+        match value {
+            Ok(value) => value,
+            Err(abort) => std::panic::panic_any(abort),
+        }
+    }};
+}
+
+/// Group the assertions in a block under a named step of the test.
+///
+/// The macro accepts a step name and a block, and evaluates to the value of the block
+///
+/// ```
+/// let session = step!("negotiate", {
+///     let session = client.negotiate();
+///     must_eq!("my_rfc_1234_section_3_version", 2, session.version);
+///     session
+/// });
+/// ```
+///
+/// Steps can be nested. The outcome and duration of each step is reported, and a panic inside a step is
+/// reported against that step.
+#[macro_export]
+macro_rules! step {
+    ( $controller_thread_ref:ident, $name:expr, $body:block ) => {{
+        let begin_result = $controller_thread_ref
+            .lock()
+            .unwrap()
+            .begin_step(&$name.to_string());
+        // Must not panic when the mutex lock is held
+        begin_result.unwrap();
+        let step_start = std::time::Instant::now();
+        let step_result = $body;
+        let end_result = $controller_thread_ref
+            .lock()
+            .unwrap()
+            .end_step(step_start.elapsed());
+        end_result.unwrap();
+        step_result
+    }};
+}
+
+/// Group the assertions in a block under a named subtest, such as one case of a table of inputs.
+///
+/// This is the same as `step!`, use whichever reads better in your test
+///
+/// ```
+/// for input in ["a", "b"] {
+///     subtest!(input, {
+///         must_eq!("my_rfc_1234_section_4_echo", input, echo(input));
+///     });
+/// }
+/// ```
+#[macro_export]
+macro_rules! subtest {
+    ( $controller_thread_ref:ident, $name:expr, $body:block ) => {{
+        cogno::step!($controller_thread_ref, $name, $body)
+    }};
+}
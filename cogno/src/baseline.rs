@@ -0,0 +1,308 @@
+use crate::error::CognoError;
+use crate::report::model::{TestDef, TestOutcome};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// A failure recorded in a baseline. An entry without an assertion id is a test which errored.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    pub spec_id: String,
+    pub test: String,
+    pub assertion_id: Option<String>,
+}
+
+impl Display for BaselineEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if !self.spec_id.is_empty() {
+            write!(f, "[{}] ", self.spec_id)?;
+        }
+
+        match &self.assertion_id {
+            Some(assertion_id) => write!(f, "{} - {}", self.test, assertion_id),
+            None => write!(f, "{} - errored", self.test),
+        }
+    }
+}
+
+/// The known failures of a program under test, used to only fail a run on regressions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub failures: BTreeSet<BaselineEntry>,
+}
+
+/// The differences between a run and a baseline.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaselineComparison {
+    /// Failures which are not in the baseline.
+    pub regressions: Vec<BaselineEntry>,
+    /// Failures in the baseline which now pass.
+    pub newly_passing: Vec<BaselineEntry>,
+}
+
+impl Baseline {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CognoError> {
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| baseline_error(path.as_ref(), e))?;
+        serde_json::from_str(&content).map_err(|e| baseline_error(path.as_ref(), e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CognoError> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&path, content).map_err(|e| baseline_error(path.as_ref(), e))
+    }
+
+    /// The failures of a run. Expected failures and tests which did not finish are not included.
+    pub fn from_tests(tests: &[TestDef]) -> Self {
+        let mut failures = BTreeSet::new();
+        for test in tests {
+            let assertion_ids: Vec<Option<String>> = match test.get_test_outcome() {
+                TestOutcome::Errored(_) => vec![None],
+                TestOutcome::AssertionsFailed(assertions) => {
+                    assertions.iter().map(|a| Some(a.id.clone())).collect()
+                }
+                _ => continue,
+            };
+
+            for spec_id in spec_ids(test) {
+                for assertion_id in &assertion_ids {
+                    failures.insert(BaselineEntry {
+                        spec_id: spec_id.clone(),
+                        test: test.name.clone(),
+                        assertion_id: assertion_id.clone(),
+                    });
+                }
+            }
+        }
+
+        Baseline { failures }
+    }
+
+    /// Compare the failures of a run with this baseline. Only tests which ran can be newly passing.
+    pub fn compare(&self, current: &Baseline, tests: &[TestDef]) -> BaselineComparison {
+        let ran = ran_tests(tests);
+
+        BaselineComparison {
+            regressions: current.failures.difference(&self.failures).cloned().collect(),
+            newly_passing: self
+                .failures
+                .difference(&current.failures)
+                .filter(|e| ran.contains(&(e.spec_id.as_str(), e.test.as_str())))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Replace the failures of the tests which ran, keeping those of tests which did not run.
+    pub fn updated(&self, current: &Baseline, tests: &[TestDef]) -> Baseline {
+        let ran = ran_tests(tests);
+
+        let mut failures: BTreeSet<BaselineEntry> = self
+            .failures
+            .iter()
+            .filter(|e| !ran.contains(&(e.spec_id.as_str(), e.test.as_str())))
+            .cloned()
+            .collect();
+        failures.extend(current.failures.iter().cloned());
+
+        Baseline { failures }
+    }
+}
+
+/// The spec and test pairs which ran to an outcome that can be compared. A test which errored may have stopped
+/// before making its assertions, so whether they still fail is not known.
+fn ran_tests(tests: &[TestDef]) -> HashSet<(&str, &str)> {
+    tests
+        .iter()
+        .filter(|t| {
+            !matches!(
+                t.get_test_outcome(),
+                TestOutcome::Skipped(_)
+                    | TestOutcome::Withdrawn(_)
+                    | TestOutcome::Inconclusive(_)
+                    | TestOutcome::Errored(_)
+            )
+        })
+        .flat_map(|t| {
+            let spec_ids: Vec<&str> = match t.spec_ids.is_empty() {
+                true => vec![""],
+                false => t.spec_ids.iter().map(|s| s.as_str()).collect(),
+            };
+            spec_ids.into_iter().map(move |s| (s, t.name.as_str()))
+        })
+        .collect()
+}
+
+/// A test without any specs is recorded with an empty spec id.
+fn spec_ids(test: &TestDef) -> Vec<String> {
+    match test.spec_ids.is_empty() {
+        true => vec![String::new()],
+        false => test.spec_ids.clone(),
+    }
+}
+
+fn baseline_error<E: Display>(path: &Path, e: E) -> CognoError {
+    CognoError::BaselineError(format!("{} - {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::model::{AssertionDef, AssertionType, SkipReason};
+
+    fn test(name: &str, spec_ids: &[&str], failed: &[&str]) -> TestDef {
+        TestDef {
+            name: name.to_string(),
+            spec_ids: spec_ids.iter().map(|s| s.to_string()).collect(),
+            tags: Vec::new(),
+            skip_reason: None,
+            inconclusive_reason: None,
+            panic_info: None,
+            returned_error: None,
+            expected_failure: None,
+            withdrawn: None,
+            crash: None,
+            output: None,
+            logs: Vec::new(),
+            completed: true,
+            assertions: failed
+                .iter()
+                .map(|id| AssertionDef {
+                    id: id.to_string(),
+                    kind: AssertionType::Must,
+                    result: false,
+                    error_message: None,
+                    expected_failure: None,
+                    withdrawn: None,
+                    modifiers: Vec::new(),
+                })
+                .collect(),
+            steps: Vec::new(),
+            previous_attempts: Vec::new(),
+        }
+    }
+
+    fn entry(spec_id: &str, test: &str, assertion_id: Option<&str>) -> BaselineEntry {
+        BaselineEntry {
+            spec_id: spec_id.to_string(),
+            test: test.to_string(),
+            assertion_id: assertion_id.map(|a| a.to_string()),
+        }
+    }
+
+    #[test]
+    fn failures_of_a_run() {
+        let mut errored = test("errored", &[], &[]);
+        errored.returned_error = Some("no connection".to_string());
+        let mut expected = test("expected", &["1234"], &["rfc_1234_sec_3"]);
+        expected.expected_failure = Some("see #42".to_string());
+        let tests = vec![
+            test("passes", &["1234"], &[]),
+            test("fails", &["1234", "1235"], &["rfc_1234_sec_8"]),
+            errored,
+            expected,
+        ];
+
+        let failures: Vec<BaselineEntry> = Baseline::from_tests(&tests).failures.into_iter().collect();
+        assert_eq!(
+            vec![
+                entry("", "errored", None),
+                entry("1234", "fails", Some("rfc_1234_sec_8")),
+                entry("1235", "fails", Some("rfc_1234_sec_8")),
+            ],
+            failures
+        );
+    }
+
+    #[test]
+    fn compare_finds_new_failures_and_fixed_failures() {
+        let baseline = Baseline::from_tests(&[
+            test("unchanged", &["1234"], &["rfc_1234_sec_1"]),
+            test("fixed", &["1234"], &["rfc_1234_sec_2"]),
+        ]);
+        let tests = vec![
+            test("unchanged", &["1234"], &["rfc_1234_sec_1"]),
+            test("fixed", &["1234"], &[]),
+            test("regressed", &["1234"], &["rfc_1234_sec_3"]),
+        ];
+
+        let comparison = baseline.compare(&Baseline::from_tests(&tests), &tests);
+        assert_eq!(vec![entry("1234", "regressed", Some("rfc_1234_sec_3"))], comparison.regressions);
+        assert_eq!(vec![entry("1234", "fixed", Some("rfc_1234_sec_2"))], comparison.newly_passing);
+    }
+
+    #[test]
+    fn tests_which_did_not_run_are_not_fixed() {
+        let baseline = Baseline::from_tests(&[test("skipped", &["1234"], &["rfc_1234_sec_1"])]);
+        let mut skipped = test("skipped", &["1234"], &[]);
+        skipped.skip_reason = Some(SkipReason::SpecDisabled);
+        let tests = vec![skipped];
+
+        let comparison = baseline.compare(&Baseline::from_tests(&tests), &tests);
+        assert!(comparison.regressions.is_empty());
+        assert!(comparison.newly_passing.is_empty());
+    }
+
+    #[test]
+    fn tests_which_errored_are_not_fixed() {
+        let baseline = Baseline::from_tests(&[test("errored", &["1234"], &["rfc_1234_sec_1"])]);
+        let mut errored = test("errored", &["1234"], &[]);
+        errored.completed = false;
+        let tests = vec![errored];
+
+        let comparison = baseline.compare(&Baseline::from_tests(&tests), &tests);
+        assert_eq!(vec![entry("1234", "errored", None)], comparison.regressions);
+        assert!(comparison.newly_passing.is_empty());
+
+        let updated = baseline.updated(&Baseline::from_tests(&tests), &tests);
+        let failures: Vec<BaselineEntry> = updated.failures.into_iter().collect();
+        assert_eq!(
+            vec![entry("1234", "errored", None), entry("1234", "errored", Some("rfc_1234_sec_1"))],
+            failures
+        );
+    }
+
+    #[test]
+    fn update_replaces_the_failures_of_tests_which_ran() {
+        let baseline = Baseline::from_tests(&[
+            test("fixed", &["1234"], &["rfc_1234_sec_1"]),
+            test("not_run", &["1235"], &["rfc_1235_sec_1"]),
+        ]);
+        let tests = vec![
+            test("fixed", &["1234"], &[]),
+            test("regressed", &["1234"], &["rfc_1234_sec_2"]),
+        ];
+
+        let updated = baseline.updated(&Baseline::from_tests(&tests), &tests);
+        let failures: Vec<BaselineEntry> = updated.failures.into_iter().collect();
+        assert_eq!(
+            vec![
+                entry("1234", "regressed", Some("rfc_1234_sec_2")),
+                entry("1235", "not_run", Some("rfc_1235_sec_1")),
+            ],
+            failures
+        );
+    }
+
+    #[test]
+    fn saved_baseline_loads_the_same_failures() {
+        let baseline = Baseline::from_tests(&[
+            test("fails", &["1234"], &["rfc_1234_sec_8"]),
+            test("no_spec", &[], &["always"]),
+        ]);
+        let path = std::env::temp_dir().join(format!("cogno-baseline-test-{}.json", std::process::id()));
+
+        baseline.save(&path).unwrap();
+        let loaded = Baseline::load(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(baseline.failures, loaded.unwrap().failures);
+    }
+
+    #[test]
+    fn missing_baseline_is_an_error() {
+        let path = std::env::temp_dir().join("cogno-baseline-test-missing.json");
+        assert!(matches!(Baseline::load(path), Err(CognoError::BaselineError(_))));
+    }
+}
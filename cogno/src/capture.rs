@@ -0,0 +1,193 @@
+use crate::error::CognoError;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Condvar, Mutex, OnceLock};
+
+/// Written into the pipe to find out when the reader has caught up with the output written before it.
+const SYNC_MARKER: &[u8] = b"\0cogno-capture-sync\0";
+
+static CAPTURE: OnceLock<Capture> = OnceLock::new();
+
+struct Capture {
+    original_stdout: Mutex<File>,
+    /// The write end of the pipe, and how many sync markers have been written to it.
+    sync_writer: Mutex<(File, u64)>,
+    state: Mutex<State>,
+    synced: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The output of each running test, by test identifier.
+    running: HashMap<usize, Vec<u8>>,
+    syncs_seen: u64,
+}
+
+/// Start capturing what tests write to stdout and stderr, including the output of processes they start.
+///
+/// The stdout and stderr of the process are redirected into a pipe, which a reader thread hands to the test which
+/// is running. Output written while no test or several tests are running is passed through. The harness writes its
+/// own output to the original stdout with [`with_stdout`].
+#[cfg(unix)]
+pub fn start() -> Result<(), CognoError> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    let error = |action: &str| {
+        CognoError::CaptureError(format!("{} - {}", action, std::io::Error::last_os_error()))
+    };
+
+    // The descriptors are owned by the files created from them, and the standard descriptors are replaced once
+    let (reader, writer, original_stdout) = unsafe {
+        let mut fds = [0; 2];
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return Err(error("could not create a pipe"));
+        }
+        for fd in fds {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+
+        let original_stdout = libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0);
+        if original_stdout < 0 {
+            return Err(error("could not copy stdout"));
+        }
+
+        let _ = std::io::stdout().flush();
+        if libc::dup2(fds[1], libc::STDOUT_FILENO) < 0 || libc::dup2(fds[1], libc::STDERR_FILENO) < 0 {
+            return Err(error("could not redirect stdout and stderr"));
+        }
+
+        (
+            File::from_raw_fd(fds[0]),
+            File::from_raw_fd(fds[1]),
+            File::from_raw_fd(original_stdout),
+        )
+    };
+
+    let capture = CAPTURE.get_or_init(|| Capture {
+        original_stdout: Mutex::new(original_stdout),
+        sync_writer: Mutex::new((writer, 0)),
+        state: Mutex::new(State::default()),
+        synced: Condvar::new(),
+    });
+
+    std::thread::Builder::new()
+        .name("cogno-capture".to_string())
+        .spawn(move || {
+            let mut reader = reader;
+            let mut buf = [0u8; 8192];
+            let mut pending = Vec::new();
+            while let Ok(n @ 1..) = reader.read(&mut buf) {
+                pending.extend_from_slice(&buf[..n]);
+                capture.deliver(&mut pending);
+            }
+        })
+        .map_err(|e| CognoError::CaptureError(format!("could not start the reader - {}", e)))?;
+
+    Ok(())
+}
+
+/// Output can only be captured on Unix, elsewhere tests write straight to the console.
+#[cfg(not(unix))]
+pub fn start() -> Result<(), CognoError> {
+    Ok(())
+}
+
+/// Start collecting the output of a test.
+pub fn begin(test_id: usize) {
+    if let Some(capture) = CAPTURE.get() {
+        // Output written before the test started belongs to the tests which were already running
+        capture.sync();
+        capture.state.lock().unwrap().running.insert(test_id, Vec::new());
+    }
+}
+
+/// Stop collecting the output of a test and return what it wrote, if anything.
+pub fn end(test_id: usize) -> Option<String> {
+    let capture = CAPTURE.get()?;
+    capture.sync();
+
+    let output = capture.state.lock().unwrap().running.remove(&test_id)?;
+    if output.is_empty() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+/// Wait until output which has been written so far has been collected or passed through.
+pub fn flush() {
+    if let Some(capture) = CAPTURE.get() {
+        capture.sync();
+    }
+}
+
+/// Write to the stdout of the process, rather than to the captured output of the tests.
+pub fn with_stdout<R>(f: impl FnOnce(&mut dyn Write) -> R) -> R {
+    match CAPTURE.get() {
+        Some(capture) => f(&mut *capture.original_stdout.lock().unwrap()),
+        None => f(&mut std::io::stdout().lock()),
+    }
+}
+
+impl Capture {
+    fn sync(&self) {
+        let _ = std::io::stdout().flush();
+
+        let ticket = {
+            let mut sync_writer = self.sync_writer.lock().unwrap();
+            if sync_writer.0.write_all(SYNC_MARKER).is_err() {
+                return;
+            }
+            sync_writer.1 += 1;
+            sync_writer.1
+        };
+
+        let mut state = self.state.lock().unwrap();
+        while state.syncs_seen < ticket {
+            state = self.synced.wait(state).unwrap();
+        }
+    }
+
+    /// Hand out the output read so far, keeping the start of a sync marker which has not been read in full.
+    fn deliver(&self, pending: &mut Vec<u8>) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(position) = find(pending, SYNC_MARKER) {
+            let output: Vec<u8> = pending.drain(..position).collect();
+            pending.drain(..SYNC_MARKER.len());
+            self.append(&mut state, &output);
+            state.syncs_seen += 1;
+            self.synced.notify_all();
+        }
+
+        let keep = (1..SYNC_MARKER.len())
+            .rev()
+            .find(|n| pending.ends_with(&SYNC_MARKER[..*n]))
+            .unwrap_or(0);
+        let output: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+        self.append(&mut state, &output);
+    }
+
+    /// Output written while several tests are running cannot be told apart, so it is passed through rather than
+    /// added to a test which may not have written it.
+    fn append(&self, state: &mut State, output: &[u8]) {
+        if output.is_empty() {
+            return;
+        }
+
+        let mut running = state.running.values_mut();
+        match (running.next(), running.next()) {
+            (Some(test_output), None) => test_output.extend_from_slice(output),
+            _ => {
+                let mut original_stdout = self.original_stdout.lock().unwrap();
+                let _ = original_stdout.write_all(output);
+                let _ = original_stdout.flush();
+            }
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
@@ -0,0 +1,61 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+
+/// A typed key for a value shared between tests in the same run.
+///
+/// Declare keys as constants so that every test uses the same name and type
+///
+/// ```
+/// const SESSION_TICKET: ContextKey<Vec<u8>> = ContextKey::new("session_ticket");
+/// ```
+///
+/// The type is part of the key, so keys with the same name but different types hold different values.
+pub struct ContextKey<T> {
+    name: &'static str,
+    value_type: PhantomData<fn() -> T>,
+}
+
+impl<T> ContextKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        ContextKey {
+            name,
+            value_type: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Used by the test harness. Not for direct use, see [`context_set!`] and [`context_get!`].
+///
+/// Values set by tests which live for the whole run.
+#[derive(Default)]
+pub struct Context {
+    values: HashMap<(&'static str, TypeId), Box<dyn Any + Send>>,
+}
+
+impl Context {
+    pub fn set<T: Any + Send>(&mut self, key: &ContextKey<T>, value: T) {
+        self.values
+            .insert((key.name, TypeId::of::<T>()), Box::new(value));
+    }
+
+    pub fn get<T: Any + Send + Clone>(&self, key: &ContextKey<T>) -> Option<T> {
+        self.values
+            .get(&(key.name, TypeId::of::<T>()))
+            .and_then(|v| v.downcast_ref::<T>())
+            .cloned()
+    }
+}
+
+impl Debug for Context {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.values.keys().map(|(name, _)| name))
+            .finish()
+    }
+}
@@ -13,5 +13,29 @@ pub enum CognoError {
     ConflictingModifiers(String),
 
     #[error("serialisation error {0}")]
-    SerialisationError(#[from] serde_json::Error)
+    SerialisationError(#[from] serde_json::Error),
+
+    #[error("format error in test vectors {0}")]
+    VectorFormatError(String),
+
+    #[error("invalid pattern {0}")]
+    InvalidPattern(String),
+
+    #[error("could not use baseline {0}")]
+    BaselineError(String),
+
+    #[error("no running test on this thread, make assertions from the test or a thread started with cogno::spawn_scoped")]
+    NoCurrentTest,
+
+    #[error("invalid isolation {0}, expected test or spec")]
+    InvalidIsolation(String),
+
+    #[error("could not capture test output {0}")]
+    CaptureError(String),
+
+    #[error("modifier for {0} disables it without a reason")]
+    MissingModifierReason(String),
+
+    #[error("invalid list format {0}, expected table or json")]
+    InvalidListFormat(String),
 }
@@ -0,0 +1,89 @@
+use crate::error::CognoError;
+use serde::{Deserialize, Serialize};
+
+/// Used by the test harness. Not for direct use.
+///
+/// The tests which `cargo cogno` discovered in the source of the suite, read from the manifest it writes.
+#[derive(Debug)]
+pub struct Inventory {
+    tests: Vec<InventoryTest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryTest {
+    pub name: String,
+    pub file: String,
+    pub spec_ids: Vec<String>,
+    pub tags: Vec<String>,
+    /// The ids of the assertions in the body of the test function.
+    pub assertion_ids: Vec<String>,
+    /// Whether this is a test factory, whose tests are only known at runtime.
+    pub factory: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestModule {
+    relative_path: String,
+    test_functions: Vec<ManifestFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestFunction {
+    name: String,
+    #[serde(default)]
+    spec_ids: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    assertion_ids: Vec<String>,
+    #[serde(default)]
+    factory: bool,
+}
+
+impl Inventory {
+    pub fn parse(manifest: &str) -> Result<Self, CognoError> {
+        let modules: Vec<ManifestModule> = serde_json::from_str(manifest)?;
+        let tests = modules
+            .into_iter()
+            .flat_map(|module| {
+                let file = module.relative_path;
+                module
+                    .test_functions
+                    .into_iter()
+                    .map(move |function| InventoryTest {
+                        name: function.name,
+                        file: file.clone(),
+                        spec_ids: function.spec_ids,
+                        tags: function.tags,
+                        assertion_ids: function.assertion_ids,
+                        factory: function.factory,
+                    })
+            })
+            .collect();
+
+        Ok(Inventory { tests })
+    }
+
+    pub fn tests(&self) -> &[InventoryTest] {
+        &self.tests
+    }
+
+    /// The discovered tests which a test id names. Tests with vectors are named after their function and the
+    /// index of the vector, such as `base16_encoding[2]`.
+    pub fn find<'a>(&'a self, test_id: &'a str) -> impl Iterator<Item = &'a InventoryTest> {
+        let function = function_name(test_id);
+        self.tests.iter().filter(move |t| t.name == function)
+    }
+}
+
+/// The name of the test function which declares a test, which is the test id without the index of a vector.
+pub fn function_name(test_id: &str) -> &str {
+    match test_id.split_once('[') {
+        Some((function, index))
+            if matches!(index.strip_suffix(']').map(str::parse::<usize>), Some(Ok(_))) =>
+        {
+            function
+        }
+        _ => test_id,
+    }
+}
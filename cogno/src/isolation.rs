@@ -0,0 +1,243 @@
+use crate::capture;
+use crate::error::CognoError;
+use crate::report::model::TestDef;
+use crate::scheduler::TestFunction;
+use crate::{FunctionResult, TestController};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Starts a line of output from a child process which holds an event rather than output from a test.
+const EVENT_MARKER: &str = "\u{1e}cogno-event ";
+
+/// How tests are separated into processes, set with `cargo cogno --isolate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Isolation {
+    /// Every test runs in this process.
+    Shared,
+    /// Each test function runs in its own child process.
+    PerTest,
+    /// The tests of each enabled spec run in their own child process.
+    PerSpec,
+    /// This process is a child which runs one test function.
+    ChildFunction(usize),
+    /// This process is a child which runs the tests of one spec, or the tests without an enabled spec when empty.
+    ChildSpec(String),
+}
+
+impl Isolation {
+    pub fn load() -> Result<Self, CognoError> {
+        if let Ok(index) = std::env::var("COGNO_ISOLATED_FUNCTION") {
+            return index
+                .parse()
+                .map(Isolation::ChildFunction)
+                .map_err(|_| CognoError::InvalidIsolation(index));
+        }
+
+        if let Ok(spec) = std::env::var("COGNO_ISOLATED_SPEC") {
+            return Ok(Isolation::ChildSpec(spec));
+        }
+
+        match std::env::var("COGNO_ISOLATE").unwrap_or_default().as_str() {
+            "" => Ok(Isolation::Shared),
+            "test" => Ok(Isolation::PerTest),
+            "spec" => Ok(Isolation::PerSpec),
+            other => Err(CognoError::InvalidIsolation(other.to_string())),
+        }
+    }
+
+    pub fn is_child(&self) -> bool {
+        matches!(self, Isolation::ChildFunction(_) | Isolation::ChildSpec(_))
+    }
+}
+
+/// What a child process tells the process which started it.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ChildEvent {
+    /// A test has been registered, so it is known if the child crashes before the test finishes.
+    Started(TestDef),
+    Finished(TestDef),
+    /// Every test has finished, with the results used to check the dependencies of later tests.
+    Done(HashMap<String, FunctionResult>),
+}
+
+/// Write an event for the parent process, which ends the line.
+pub fn send_event(event: &ChildEvent) {
+    let line = serde_json::to_string(event).expect("test results can always be serialised");
+    capture::with_stdout(|stdout| {
+        let _ = writeln!(stdout, "{}{}", EVENT_MARKER, line);
+        let _ = stdout.flush();
+    });
+}
+
+/// The results of earlier tests, passed from the parent process.
+pub fn load_function_results() -> HashMap<String, FunctionResult> {
+    std::env::var("COGNO_FUNCTION_RESULTS")
+        .ok()
+        .and_then(|results| serde_json::from_str(&results).ok())
+        .unwrap_or_default()
+}
+
+/// Run one test function in a child process.
+pub fn run_function_in_child(
+    controller: &Arc<Mutex<TestController>>,
+    index: usize,
+    function: &TestFunction,
+) {
+    run_child(
+        controller,
+        ("COGNO_ISOLATED_FUNCTION", index.to_string()),
+        function.name,
+        Some(function.name),
+    );
+}
+
+/// Run the tests of each enabled spec in a child process, one spec after another, then the tests without
+/// an enabled spec.
+pub fn run_specs_in_children(controller: &Arc<Mutex<TestController>>) {
+    let specs = controller.lock().unwrap().enabled_specs();
+    for spec in specs {
+        run_child(
+            controller,
+            ("COGNO_ISOLATED_SPEC", spec.clone()),
+            &format!("spec {}", spec),
+            None,
+        );
+    }
+
+    run_child(
+        controller,
+        ("COGNO_ISOLATED_SPEC", String::new()),
+        "tests without an enabled spec",
+        None,
+    );
+}
+
+/// Start a copy of this process to run some of the tests and record the results it sends back.
+///
+/// If the child crashes, the tests which were running are recorded as errored with how the child ended. A
+/// crash outside of a test is recorded against a test named after what the child was running.
+fn run_child(
+    controller: &Arc<Mutex<TestController>>,
+    scope: (&str, String),
+    label: &str,
+    function: Option<&str>,
+) {
+    let function_results = controller.lock().unwrap().function_results_json();
+    let mut child = Command::new(std::env::current_exe().expect("could not find the test suite binary"))
+        .args(std::env::args_os().skip(1))
+        .env_remove("COGNO_ISOLATE")
+        .env_remove("COGNO_BASELINE")
+        .env_remove("COGNO_UPDATE_BASELINE")
+        .env(scope.0, scope.1)
+        .env("COGNO_FUNCTION_RESULTS", function_results)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("could not start the test suite binary");
+
+    let mut started: Vec<TestDef> = Vec::new();
+    let mut done = false;
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = Vec::new();
+    while stdout.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+        let text = String::from_utf8_lossy(&line);
+        match text.split_once(EVENT_MARKER) {
+            // Output from a test may not end with a new line, so the event can follow it on the same line
+            Some((output, event)) => {
+                print!("{}", output);
+                match serde_json::from_str(event) {
+                    Ok(ChildEvent::Started(test_def)) => started.push(test_def),
+                    Ok(ChildEvent::Finished(test_def)) => {
+                        if let Some(index) = started.iter().position(|t| t.name == test_def.name) {
+                            started.remove(index);
+                        }
+                        controller.lock().unwrap().add_isolated_test(test_def);
+                    }
+                    Ok(ChildEvent::Done(results)) => {
+                        controller.lock().unwrap().merge_function_results(results);
+                        done = true;
+                    }
+                    Err(e) => {
+                        tracing::event!(tracing::Level::WARN, "unreadable event from child - {}", e);
+                    }
+                }
+            }
+            None => print!("{}", text),
+        }
+        line.clear();
+    }
+
+    let status = child.wait().expect("could not wait for the test suite binary");
+    if done && status.success() {
+        return;
+    }
+
+    let crash = describe_exit(status, done);
+    tracing::event!(tracing::Level::INFO, "child for {} crashed - {}", label, crash);
+
+    let mut controller_handle = controller.lock().unwrap();
+    if started.is_empty() {
+        started.push(TestDef {
+            name: label.to_string(),
+            spec_ids: Vec::new(),
+            tags: Vec::new(),
+            skip_reason: None,
+            inconclusive_reason: None,
+            panic_info: None,
+            returned_error: None,
+            expected_failure: None,
+            withdrawn: None,
+            crash: None,
+            output: None,
+            logs: Vec::new(),
+            completed: false,
+            assertions: Vec::new(),
+            steps: Vec::new(),
+            previous_attempts: Vec::new(),
+        });
+    }
+
+    for mut test_def in started {
+        test_def.crash = Some(crash.clone());
+        controller_handle.add_isolated_test(test_def);
+    }
+
+    if let Some(function) = function {
+        controller_handle.merge_function_results(HashMap::from([(
+            function.to_string(),
+            FunctionResult::Failed,
+        )]));
+    }
+}
+
+fn describe_exit(status: ExitStatus, done: bool) -> String {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return match signal_name(signal) {
+            Some(name) => format!("killed by signal {} ({})", signal, name),
+            None => format!("killed by signal {}", signal),
+        };
+    }
+
+    match status.code() {
+        Some(code) if !done => format!("exited with code {} before reporting its results", code),
+        Some(code) => format!("exited with code {}", code),
+        None => "exited without a status".to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<&'static str> {
+    match signal {
+        4 => Some("SIGILL"),
+        6 => Some("SIGABRT"),
+        8 => Some("SIGFPE"),
+        9 => Some("SIGKILL"),
+        11 => Some("SIGSEGV"),
+        15 => Some("SIGTERM"),
+        _ => None,
+    }
+}
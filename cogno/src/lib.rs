@@ -1,51 +1,164 @@
 #![doc = include_str!("../README.md")]
 
+use crate::baseline::Baseline;
+use crate::context::Context;
 use crate::error::CognoError;
+use crate::isolation::{ChildEvent, Isolation};
+use crate::pattern::Pattern;
 use crate::report::model::{
-    is_a_not_assertion, is_passed_assertion, AssertionDef, AssertionType, TestDef,
+    is_a_not_assertion, is_passed_assertion, AppliedModifier, AssertionDef, AssertionType, SkipReason, StepDef,
+    Summary, TestDef, TestOutcome, Withdrawal,
+};
+use crate::report::{ChildReporter, Reporter, RawReporter};
+use crate::spec::{
+    load_spec_modifier, modifier_paths, most_specific, AssertionModifier, ModifierAction, ModifierFile, TestModifier,
 };
-use crate::report::{Reporter, RawReporter};
-use crate::spec::{load_spec_modifier, AssertionModifier, SpecModifier};
 pub use assert::*;
+pub use context::ContextKey;
 pub use cogno_attr::*;
 pub use proc::*;
-use itertools::Itertools;
-use std::collections::HashSet;
+pub use runner::{run_factory, run_test, spawn, spawn_scoped, TestAbort, TestFactory, TestMeta, TestReturn};
+pub use scheduler::{run_test_functions, TestFunction};
+pub use inventory::Inventory;
+pub use listing::{list_format, print_listing, ListFormat};
+pub use spec::check_modifiers;
+pub use logs::TestLogLayer;
+pub use report::model::{PanicDef, Verdict};
+pub use vectors::parse_vectors;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
+use std::time::Duration;
 #[cfg(feature = "console")]
 use crate::report::ConsoleReporter;
 
 pub extern crate tracing;
 pub extern crate tracing_subscriber;
 
+mod baseline;
+mod capture;
+mod context;
 mod error;
+mod inventory;
+mod isolation;
+mod listing;
+mod logs;
+mod pattern;
 mod report;
 mod spec;
 mod assert;
 mod proc;
+mod runner;
+mod scheduler;
+mod vectors;
 
 /// Used by the test harness. Not for direct use.
 ///
 /// Holds test state by recording assertions and test metadata. It drives the test reporter as
-/// assertions arrive.
+/// tests finish.
+///
+/// Tests can run at the same time, so each test is identified by its index in `tests`. Assertions are
+/// attributed to the test running on the current thread.
 #[derive(Debug)]
 pub struct TestController {
     tests: Vec<TestDef>,
     specs: HashSet<String>,
-    modifiers: Vec<SpecModifier>,
+    tags: HashSet<String>,
+    exclude_tags: HashSet<String>,
+    name_filter: Option<Pattern>,
+    modifiers: Vec<ModifierFile>,
+    /// Whether later modifier files override earlier ones rather than conflicting with them.
+    layered_modifiers: bool,
     reporter: Box<dyn Reporter>,
+    function_results: HashMap<String, FunctionResult>,
+    context: Context,
+    /// The running tests, with the path to the innermost step of each which has not ended.
+    running: HashMap<usize, Vec<usize>>,
+    /// The index of the test function which registered each test, if it was run by the scheduler.
+    test_functions: Vec<Option<usize>>,
+    /// Finished tests waiting for the tests of earlier functions to be reported, by function index.
+    pending_reports: BTreeMap<usize, Vec<usize>>,
+    /// The index of the first test function which has not finished.
+    next_function: usize,
+    finished_functions: HashSet<usize>,
+    baseline_path: Option<String>,
+    baseline: Option<Baseline>,
+    update_baseline: bool,
+    retries: u32,
+    /// How many test functions can run at the same time.
+    jobs: usize,
+    isolation: Isolation,
+}
+
+/// The combined result of the tests declared by one test function, used to check dependencies.
+///
+/// Ordered so that the worst result of any test is the result of the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum FunctionResult {
+    Passed,
+    NotRun,
+    Failed,
 }
 
 impl TestController {
     pub fn new() -> Result<Self, CognoError> {
-        let specs = load_specs();
-        let modifiers = load_modifiers()?;
+        let specs = load_list("COGNO_SPECS");
+        let tags = load_list("COGNO_TAGS");
+        let exclude_tags = load_list("COGNO_EXCLUDE_TAGS");
+        let name_filter = load_name_filter()?;
+        let modifiers = load_modifiers(&specs)?;
+        let baseline_path = std::env::var("COGNO_BASELINE").ok().filter(|p| !p.is_empty());
+        let update_baseline = "true"
+            == std::env::var("COGNO_UPDATE_BASELINE")
+                .unwrap_or(String::from("false"))
+                .as_str();
+        let baseline = load_baseline(baseline_path.as_deref(), update_baseline)?;
+        let isolation = Isolation::load()?;
+        let nocapture = "true"
+            == std::env::var("COGNO_NOCAPTURE")
+                .unwrap_or(String::from("false"))
+                .as_str();
+        // With isolated tests the child processes capture the output of the tests they run
+        if !nocapture && !matches!(isolation, Isolation::PerTest | Isolation::PerSpec) {
+            capture::start()?;
+        }
+        // A child process sends its results to the parent process which reports them
+        let reporter: Box<dyn Reporter> = if isolation.is_child() {
+            Box::new(ChildReporter)
+        } else {
+            create_reporter()
+        };
 
         Ok(TestController {
             tests: Vec::new(),
             specs,
+            tags,
+            exclude_tags,
+            name_filter,
             modifiers,
-            reporter: create_reporter(),
+            layered_modifiers: spec::is_layered(),
+            reporter,
+            function_results: isolation::load_function_results(),
+            context: Context::default(),
+            running: HashMap::new(),
+            test_functions: Vec::new(),
+            pending_reports: BTreeMap::new(),
+            next_function: 0,
+            finished_functions: HashSet::new(),
+            baseline_path,
+            baseline,
+            update_baseline,
+            retries: std::env::var("COGNO_RETRIES")
+                .ok()
+                .and_then(|r| r.parse().ok())
+                .unwrap_or(0),
+            jobs: std::env::var("COGNO_JOBS")
+                .ok()
+                .and_then(|j| j.parse().ok())
+                .filter(|j| *j > 0)
+                .unwrap_or(1),
+            isolation,
         })
     }
 
@@ -53,34 +166,350 @@ impl TestController {
         self.specs.contains(spec)
     }
 
-    pub fn register(&mut self, name: &str, spec_id: &str) {
+    /// A test without any specs is always enabled, otherwise at least one of its specs must be enabled.
+    pub fn is_any_spec_enabled(&self, specs: &[String]) -> bool {
+        specs.is_empty() || specs.iter().any(|s| self.is_spec_enabled(s))
+    }
+
+    /// A test is disabled if it has any excluded tag. If tags have been selected then the test must
+    /// have at least one of them.
+    pub fn is_tag_enabled(&self, tags: &[String]) -> bool {
+        if tags.iter().any(|t| self.exclude_tags.contains(t)) {
+            return false;
+        }
+
+        self.tags.is_empty() || tags.iter().any(|t| self.tags.contains(t))
+    }
+
+    /// A test is enabled if no name filter was given, otherwise its name must match the filter.
+    pub fn is_name_enabled(&self, name: &str) -> bool {
+        match &self.name_filter {
+            Some(filter) => filter.is_match(name),
+            None => true,
+        }
+    }
+
+    /// The reason to skip a test because of its dependencies, if any dependency did not pass.
+    pub fn dependency_skip_reason(&self, depends_on: &[String]) -> Option<SkipReason> {
+        depends_on
+            .iter()
+            .find_map(|d| match self.function_results.get(d) {
+                Some(FunctionResult::Passed) => None,
+                Some(FunctionResult::Failed) => Some(SkipReason::DependencyFailed(d.clone())),
+                Some(FunctionResult::NotRun) | None => Some(SkipReason::DependencyNotRun(d.clone())),
+            })
+    }
+
+    /// Record the outcome of a test against the function which declared it.
+    fn record_function_result(&mut self, test_id: usize, function: &str) {
+        let result = match self.tests[test_id].get_test_outcome() {
+            TestOutcome::Passed | TestOutcome::XPass(_) | TestOutcome::Flaky => {
+                FunctionResult::Passed
+            }
+            TestOutcome::Skipped(_) | TestOutcome::Withdrawn(_) => FunctionResult::NotRun,
+            _ => FunctionResult::Failed,
+        };
+
+        let function_result = self
+            .function_results
+            .entry(function.to_string())
+            .or_insert(result);
+        *function_result = (*function_result).max(result);
+    }
+
+    /// Add a test to the report and return its identifier, which is used to record its results.
+    pub fn register(&mut self, meta: &TestMeta) -> usize {
         self.tests.push(TestDef {
-            name: name.to_string(),
-            spec_id: spec_id.to_string(),
+            name: meta.name.clone(),
+            spec_ids: meta.spec_ids.clone(),
+            tags: meta.tags.clone(),
+            skip_reason: None,
+            inconclusive_reason: None,
             panic_info: None,
+            returned_error: None,
+            expected_failure: meta.expect_fail.clone(),
+            withdrawn: None,
+            crash: None,
+            output: None,
+            logs: Vec::new(),
             completed: false,
             assertions: Vec::new(),
+            steps: Vec::new(),
+            previous_attempts: Vec::new(),
         });
+        self.test_functions.push(runner::current_function());
+
+        let test_id = self.tests.len() - 1;
+        self.running.insert(test_id, Vec::new());
+        capture::begin(test_id);
+        self.reporter.start(&self.tests[test_id]);
+        test_id
     }
 
-    pub fn set_panic_info(&mut self, info: String) {
-        if self.tests.is_empty() {
-            return;
+    /// The test running on the current thread. Threads spawned by a test are not known, so they are
+    /// attributed to the only running test if there is just one. A thread running a test function, such as a test
+    /// factory outside of any of its tests, is never attributed to another test.
+    fn current_test_id(&self) -> Option<usize> {
+        match runner::current_test() {
+            Some(id) => Some(id).filter(|id| self.running.contains_key(id)),
+            None if runner::current_function().is_some() => None,
+            None => match self.running.len() {
+                1 => self.running.keys().next().copied(),
+                _ => None,
+            },
         }
+    }
+
+    /// How many test functions can run at the same time.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    pub(crate) fn isolation(&self) -> &Isolation {
+        &self.isolation
+    }
 
-        let current_test = self.tests.last_mut().unwrap();
-        current_test.panic_info = Some(info);
-        self.reporter.report(current_test);
+    /// The enabled specs, sorted so that isolated runs are repeatable.
+    pub(crate) fn enabled_specs(&self) -> Vec<String> {
+        let mut specs: Vec<String> = self.specs.iter().cloned().collect();
+        specs.sort();
+        specs
     }
 
-    pub fn complete(&mut self) {
-        let current_test = self.tests.last_mut().unwrap();
-        current_test.completed = true;
-        self.reporter.report(current_test);
+    /// Whether a test belongs to the spec this child process runs. Each test belongs to the first of its specs
+    /// which is enabled, and tests without an enabled spec belong to the child for the empty spec.
+    pub(crate) fn is_in_isolated_spec(&self, spec_ids: &[String]) -> bool {
+        match &self.isolation {
+            Isolation::ChildSpec(spec) => {
+                let first_enabled = spec_ids.iter().find(|s| self.is_spec_enabled(s));
+                first_enabled.map(|s| s.as_str()).unwrap_or("") == spec
+            }
+            _ => true,
+        }
     }
 
-    pub fn finalize(&self) -> Result<(), CognoError> {
-        self.reporter.finalize()
+    pub(crate) fn function_results_json(&self) -> String {
+        serde_json::to_string(&self.function_results).unwrap_or_default()
+    }
+
+    /// Record the results of the test functions run by a child process.
+    fn merge_function_results(&mut self, results: HashMap<String, FunctionResult>) {
+        for (function, result) in results {
+            let function_result = self.function_results.entry(function).or_insert(result);
+            *function_result = (*function_result).max(result);
+        }
+    }
+
+    /// Report a test which was run by a child process.
+    fn add_isolated_test(&mut self, test_def: TestDef) {
+        self.tests.push(test_def);
+        self.test_functions.push(runner::current_function());
+        self.report_in_order(self.tests.len() - 1);
+    }
+
+    /// The number of times to retry a test which fails, set on the test or for the whole run.
+    pub fn retries_for(&self, meta: &TestMeta) -> u32 {
+        meta.retries.unwrap_or(self.retries)
+    }
+
+    /// Whether the test failed in a way that retrying could change.
+    pub fn is_retryable(&self, test_id: usize) -> bool {
+        self.tests[test_id].is_retryable()
+    }
+
+    /// Start another attempt of a test, keeping the results of the previous attempt.
+    pub fn retry(&mut self, test_id: usize) {
+        self.tests[test_id].begin_attempt();
+        self.running.insert(test_id, Vec::new());
+    }
+
+    /// Report a test once it will not run again.
+    ///
+    /// Tests are reported in the order of their test functions, so a test which finishes before the tests
+    /// of an earlier function waits for them.
+    pub fn finish(&mut self, test_id: usize, function: &str) {
+        self.running.remove(&test_id);
+        self.tests[test_id].output = capture::end(test_id);
+        self.tests[test_id].logs = logs::take(test_id);
+        self.record_function_result(test_id, function);
+        self.report_in_order(test_id);
+    }
+
+    fn report_in_order(&mut self, test_id: usize) {
+        match self.test_functions[test_id] {
+            Some(index) if index > self.next_function => {
+                self.pending_reports.entry(index).or_default().push(test_id);
+            }
+            _ => self.reporter.report(&self.tests[test_id]),
+        }
+    }
+
+    /// Used by the test harness. Not for direct use.
+    ///
+    /// Record that every test of a test function has finished, and report any tests which were waiting on it.
+    pub fn function_done(&mut self, index: usize) {
+        self.finished_functions.insert(index);
+        while self.finished_functions.remove(&self.next_function) {
+            self.next_function += 1;
+            for test_id in self.pending_reports.remove(&self.next_function).unwrap_or_default() {
+                self.reporter.report(&self.tests[test_id]);
+            }
+        }
+    }
+
+    /// Start a step inside the current step, or at the top level of the current test.
+    pub fn begin_step(&mut self, name: &str) -> Result<(), CognoError> {
+        let test_id = self.current_test_id().ok_or(CognoError::NoCurrentTest)?;
+        let step = StepDef {
+            name: name.to_string(),
+            panic_info: None,
+            completed: false,
+            duration: None,
+            assertions: Vec::new(),
+            steps: Vec::new(),
+        };
+
+        let index = match self.current_step_mut(test_id) {
+            Some(parent) => {
+                parent.steps.push(step);
+                parent.steps.len() - 1
+            }
+            None => {
+                let current_test = &mut self.tests[test_id];
+                current_test.steps.push(step);
+                current_test.steps.len() - 1
+            }
+        };
+        self.running.get_mut(&test_id).unwrap().push(index);
+        Ok(())
+    }
+
+    pub fn end_step(&mut self, duration: Duration) -> Result<(), CognoError> {
+        let test_id = self.current_test_id().ok_or(CognoError::NoCurrentTest)?;
+        if let Some(step) = self.current_step_mut(test_id) {
+            step.completed = true;
+            step.duration = Some(duration);
+        }
+        self.running.get_mut(&test_id).unwrap().pop();
+        Ok(())
+    }
+
+    fn current_step_mut(&mut self, test_id: usize) -> Option<&mut StepDef> {
+        let (first, rest) = self.running.get(&test_id)?.split_first()?;
+        let mut step = self.tests[test_id].steps.get_mut(*first)?;
+        for index in rest {
+            step = step.steps.get_mut(*index)?;
+        }
+        Some(step)
+    }
+
+    /// Record a test which will not run, so that it still appears in the report.
+    pub fn skip(&mut self, meta: &TestMeta, reason: SkipReason) {
+        let test_id = self.register(meta);
+        self.skip_test(test_id, reason);
+        self.finish(test_id, &meta.function);
+    }
+
+    /// Record a test which a modifier has disabled, so that it is reported as withdrawn without running.
+    pub fn withdraw(&mut self, meta: &TestMeta, withdrawal: Withdrawal) {
+        let test_id = self.register(meta);
+        self.tests[test_id].withdrawn = Some(withdrawal);
+        self.finish(test_id, &meta.function);
+    }
+
+    /// Record that a test stopped early because it could not run.
+    pub fn skip_test(&mut self, test_id: usize, reason: SkipReason) {
+        self.tests[test_id].skip_reason = Some(reason);
+    }
+
+    /// Record a panic against the test running on the current thread, and the step it happened in. Returns false
+    /// if no test is running on the thread, in which case the panic is not recorded.
+    pub fn set_panic_info(&mut self, info: PanicDef) -> bool {
+        let test_id = match self.current_test_id() {
+            Some(test_id) => test_id,
+            None => return false,
+        };
+
+        if let Some(step) = self.current_step_mut(test_id) {
+            step.panic_info = Some(info.clone());
+        }
+
+        self.tests[test_id].panic_info = Some(info);
+        true
+    }
+
+    /// Record a panic outside of any test, such as in a test factory, as an errored test named after the function.
+    pub(crate) fn function_panicked(&mut self, meta: &TestMeta, info: PanicDef) {
+        let test_id = self.register(meta);
+        self.tests[test_id].panic_info = Some(info);
+        self.finish(test_id, &meta.function);
+    }
+
+    /// Record that a test stopped early because its result could not be determined.
+    pub fn set_inconclusive(&mut self, test_id: usize, reason: String) {
+        self.tests[test_id].inconclusive_reason = Some(reason);
+    }
+
+    /// Record that a test returned an error.
+    pub fn set_returned_error(&mut self, test_id: usize, error: String) {
+        self.tests[test_id].returned_error = Some(error);
+    }
+
+    pub fn complete(&mut self, test_id: usize) {
+        self.tests[test_id].completed = true;
+    }
+
+    /// Finish the report and return whether the run succeeded.
+    ///
+    /// Without a baseline a run succeeds unless it is non-conformant. With a baseline a run succeeds unless there
+    /// are failures which are not in the baseline, and always succeeds when the baseline is being updated.
+    pub fn finalize(&mut self) -> Result<bool, CognoError> {
+        capture::flush();
+        if self.isolation.is_child() {
+            isolation::send_event(&ChildEvent::Done(self.function_results.clone()));
+            return Ok(true);
+        }
+
+        // Report any tests still waiting on an earlier function
+        for test_id in std::mem::take(&mut self.pending_reports).into_values().flatten() {
+            self.reporter.report(&self.tests[test_id]);
+        }
+
+        let mut summary = Summary::default();
+        self.tests.iter().for_each(|t| summary.add(t));
+        let mut succeeded = summary.verdict != Verdict::NonConformant;
+
+        if let (Some(path), Some(baseline)) = (&self.baseline_path, &self.baseline) {
+            let current = Baseline::from_tests(&self.tests);
+            let comparison = baseline.compare(&current, &self.tests);
+            succeeded = comparison.regressions.is_empty();
+            self.reporter.report_baseline(&comparison);
+
+            if self.update_baseline {
+                baseline.updated(&current, &self.tests).save(path)?;
+                succeeded = true;
+            }
+        }
+
+        self.reporter.finalize()?;
+        Ok(succeeded)
+    }
+
+    /// Store a value for later tests in the run, replacing any value already set for the key.
+    pub fn context_set<T: Any + Send>(&mut self, key: &ContextKey<T>, value: T) {
+        self.context.set(key, value);
+    }
+
+    /// A copy of the value stored for the key, if a test has set one. In a child process the value may have been
+    /// stored by a test in another process, so the test is stopped and skipped rather than seeing no value.
+    pub fn context_get<T: Any + Send + Clone>(&self, key: &ContextKey<T>) -> Result<Option<T>, TestAbort> {
+        match self.context.get(key) {
+            None if self.isolation.is_child() => Err(TestAbort::Skip(format!(
+                "context `{}` is not shared between isolated tests",
+                key.name()
+            ))),
+            value => Ok(value),
+        }
     }
 
     pub fn must_eq<T: PartialEq + Debug>(
@@ -145,55 +574,126 @@ impl TestController {
             Some(format!("expected [{:?}] but was [{:?}]", expected, actual))
         };
 
-        let kind = self.assertion_or_override(id.to_string(), kind)?;
+        let test_id = self.current_test_id().ok_or(CognoError::NoCurrentTest)?;
+
+        // Each modifier in the chain changes what the modifiers before it set
+        let mut kind = kind;
+        let mut expected_failure = None;
+        let mut withdrawn = None;
+        let mut modifiers = Vec::new();
+        for (file, spec_id, modifier) in self.find_assertion_modifiers(test_id, id)? {
+            if let Some(assertion_type) = &modifier.assertion_type {
+                kind = assertion_type.clone();
+            }
+            if let Some(reason) = &modifier.expect_fail {
+                expected_failure = Some(reason.clone());
+            }
+            if let Some(withdrawal) = withdrawal(spec_id, modifier.action, &modifier.reason) {
+                withdrawn = Some(withdrawal);
+            }
+
+            modifiers.push(AppliedModifier {
+                source: file.location(&modifier.assertion_id.id),
+                spec_id: spec_id.to_string(),
+                assertion_type: modifier.assertion_type.clone(),
+                expect_fail: modifier.expect_fail.clone(),
+                disable_reason: modifier.action.and(modifier.reason.clone()),
+            });
+        }
 
         let def = AssertionDef {
             id: id.to_string(),
             kind,
             result,
             error_message,
+            expected_failure,
+            withdrawn,
+            modifiers,
         };
 
-        self.tests.last_mut().unwrap().assertions.push(def);
+        match self.current_step_mut(test_id) {
+            Some(step) => step.assertions.push(def),
+            None => self.tests[test_id].assertions.push(def),
+        }
 
         Ok(())
     }
 
-    fn assertion_or_override(
+    /// The modifiers for an assertion of a test in the order they apply, with the file and spec each was given for.
+    ///
+    /// Modifier ids can be patterns, and the most specific modifiers which match take precedence. The assertion
+    /// id is compared first, then the test id, so an exact assertion id beats any pattern. Equally specific
+    /// modifiers which change the assertion in different ways conflict.
+    ///
+    /// Without layering at most one modifier applies. With layering each modifier file is a layer over the files
+    /// before it, so the most specific modifier of each file applies in turn and only modifiers in the same file
+    /// can conflict.
+    fn find_assertion_modifiers(
         &self,
-        assertion_id: String,
-        original_assertion_type: AssertionType,
-    ) -> Result<AssertionType, CognoError> {
-        let current_test = self.tests.last().unwrap();
+        test_id: usize,
+        assertion_id: &str,
+    ) -> Result<Vec<MatchedModifier<'_>>, CognoError> {
+        let current_test = &self.tests[test_id];
+
+        let mut layers: Vec<Vec<(MatchedModifier, &TestModifier, &AssertionModifier)>> = Vec::new();
+        for file in &self.modifiers {
+            let matched_assertions = file
+                .modifiers
+                .spec_modifiers
+                .iter()
+                .filter(|sm| current_test.spec_ids.contains(sm.spec_id.get_ref()))
+                .flat_map(move |sm| {
+                    sm.test_modifiers
+                        .iter()
+                        .filter(|tm| tm.test_id.is_test_match(&current_test.name))
+                        .flat_map(move |tm| {
+                            tm.assertion_modifiers
+                                .iter()
+                                .filter(move |am| am.assertion_id.is_match(assertion_id))
+                                .map(move |am| ((file, sm.spec_id.get_ref().as_str(), am), tm, am))
+                        })
+                });
+
+            match layers.last_mut() {
+                Some(layer) if !self.layered_modifiers => layer.extend(matched_assertions),
+                _ => layers.push(matched_assertions.collect()),
+            }
+        }
+
+        let mut chain = Vec::new();
+        for layer in layers {
+            chain.extend(most_specific(&layer)?);
+        }
+
+        Ok(chain)
+    }
 
-        let matched_assertions: HashSet<&AssertionModifier> = self
-            .modifiers
+    /// Why a test is withdrawn, if a modifier for one of its specs disables it. The most specific test id
+    /// which matches takes precedence.
+    pub fn test_withdrawal(&self, meta: &TestMeta) -> Option<Withdrawal> {
+        self.modifiers
             .iter()
-            .filter(|sm| sm.spec_id == current_test.spec_id)
+            .flat_map(|file| &file.modifiers.spec_modifiers)
+            .filter(|sm| meta.spec_ids.contains(sm.spec_id.get_ref()))
             .flat_map(|sm| {
                 sm.test_modifiers
                     .iter()
-                    .filter(|tm| tm.test_id == current_test.name)
-                    .flat_map(|tm| {
-                        tm.assertion_modifiers
-                            .iter()
-                            .filter(|am| am.assertion_id == assertion_id)
-                    })
+                    .filter(|tm| tm.action.is_some() && tm.test_id.is_test_match(&meta.name))
+                    .map(move |tm| (sm, tm))
             })
-            .collect();
+            .min_by_key(|(_, tm)| std::cmp::Reverse(tm.test_id.specificity()))
+            .and_then(|(sm, tm)| withdrawal(sm.spec_id.get_ref(), tm.action, &tm.reason))
+    }
+}
 
-        if matched_assertions.len() > 1 {
-            return Err(CognoError::ConflictingModifiers(format!(
-                "{:?}",
-                matched_assertions
-            )));
-        }
+/// A modifier which applies to an assertion, with the file and spec it was given for.
+type MatchedModifier<'a> = (&'a ModifierFile, &'a str, &'a AssertionModifier);
 
-        Ok(match matched_assertions.iter().collect_vec().first() {
-            Some(ma) => ma.assertion_type.clone(),
-            None => original_assertion_type,
-        })
-    }
+fn withdrawal(spec_id: &str, action: Option<ModifierAction>, reason: &Option<String>) -> Option<Withdrawal> {
+    action.map(|ModifierAction::Disable| Withdrawal {
+        spec_id: spec_id.to_string(),
+        reason: reason.clone().unwrap_or_default(),
+    })
 }
 
 fn create_reporter() -> Box<dyn Reporter> {
@@ -215,25 +715,44 @@ fn create_reporter() -> Box<dyn Reporter> {
     }
 }
 
-fn load_specs() -> HashSet<String> {
-    std::env::var("COGNO_SPECS")
+fn load_list(var: &str) -> HashSet<String> {
+    std::env::var(var)
         .unwrap_or(String::new())
         .split(",")
+        .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
         .collect()
 }
 
-fn load_modifiers() -> Result<Vec<SpecModifier>, CognoError> {
-    let mut modifiers = Vec::new();
-    for f in std::env::var("COGNO_MODIFIERS")
-        .unwrap_or("".to_string())
-        .split(",")
-    {
-        if f.is_empty() {
-            continue;
-        }
+fn load_name_filter() -> Result<Option<Pattern>, CognoError> {
+    let filter = match std::env::var("COGNO_TEST_FILTER") {
+        Ok(filter) if !filter.is_empty() => filter,
+        _ => return Ok(None),
+    };
+
+    if "true" == std::env::var("COGNO_TEST_EXACT").unwrap_or(String::from("false")).as_str() {
+        Ok(Some(Pattern::exact(&filter)))
+    } else {
+        Pattern::search(&filter).map(Some)
+    }
+}
 
-        modifiers.extend(load_spec_modifier(f)?.spec_modifiers);
+fn load_baseline(path: Option<&str>, update: bool) -> Result<Option<Baseline>, CognoError> {
+    match path {
+        // The first update creates the baseline
+        Some(path) if update && !std::path::Path::new(path).exists() => Ok(Some(Baseline::default())),
+        Some(path) => Baseline::load(path).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Load the modifier files, keeping the modifiers whose conditions hold for the enabled specs.
+fn load_modifiers(specs: &HashSet<String>) -> Result<Vec<ModifierFile>, CognoError> {
+    let mut modifiers = Vec::new();
+    for f in modifier_paths() {
+        let mut file = load_spec_modifier(&f)?;
+        file.modifiers.spec_modifiers.retain(|sm| sm.is_active(specs));
+        modifiers.push(file);
     }
 
     Ok(modifiers)
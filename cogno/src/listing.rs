@@ -0,0 +1,95 @@
+use crate::error::CognoError;
+use crate::inventory::Inventory;
+use std::io::Write;
+
+/// How to print the tests of the suite, set with `cargo cogno list --format` or `--list` on the suite binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Table,
+    Json,
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Whether the suite should list its tests rather than run them, from `COGNO_LIST` or the `--list` argument of the
+/// suite binary. `--format` only chooses how to list the tests when given with `--list`.
+pub fn list_format() -> Result<Option<ListFormat>, CognoError> {
+    let mut list = false;
+    let mut format_arg = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--format" => format_arg = args.next(),
+            _ => {
+                if let Some(value) = arg.strip_prefix("--format=") {
+                    format_arg = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    let format = match std::env::var("COGNO_LIST") {
+        Ok(format) => Some(format),
+        Err(_) if list => Some(format_arg.unwrap_or_default()),
+        Err(_) => None,
+    };
+
+    match format.as_deref() {
+        None => Ok(None),
+        Some("table") | Some("") => Ok(Some(ListFormat::Table)),
+        Some("json") => Ok(Some(ListFormat::Json)),
+        Some(other) => Err(CognoError::InvalidListFormat(other.to_string())),
+    }
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Print every test in the manifest with its specs, tags, source file and the ids of the assertions in its body.
+pub fn print_listing(inventory: &Inventory, format: ListFormat) -> Result<(), CognoError> {
+    let tests = inventory.tests();
+
+    let mut out = std::io::stdout().lock();
+    match format {
+        ListFormat::Json => {
+            let _ = writeln!(out, "{}", serde_json::to_string_pretty(&tests)?);
+        }
+        ListFormat::Table => {
+            let rows: Vec<[String; 5]> = tests
+                .iter()
+                .map(|test| {
+                    [
+                        test.name.clone(),
+                        test.spec_ids.join(", "),
+                        test.tags.join(", "),
+                        test.file.clone(),
+                        test.assertion_ids.join(", "),
+                    ]
+                })
+                .collect();
+            print_table(&mut out, ["TEST", "SPECS", "TAGS", "FILE", "ASSERTIONS"], &rows);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_table(out: &mut impl Write, header: [&str; 5], rows: &[[String; 5]]) {
+    let mut widths = header.map(|h| h.len());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.map(|h| h.to_string());
+    for row in std::iter::once(&header).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        let _ = writeln!(out, "{}", line.join("  ").trim_end());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::report::model::LogRecord;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The events collected for each running test, by test identifier.
+static LOGS: Mutex<BTreeMap<usize, Vec<LogRecord>>> = Mutex::new(BTreeMap::new());
+
+/// Used by the test harness. Not for direct use.
+///
+/// A tracing layer which collects the events emitted inside each test, including events from the libraries
+/// it calls, so that they can be added to the report. Events from the test harness itself are left out.
+pub struct TestLogLayer {
+    max_level: Level,
+}
+
+impl TestLogLayer {
+    pub fn new() -> Self {
+        TestLogLayer {
+            max_level: std::env::var("COGNO_LOG_LEVEL")
+                .ok()
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(Level::INFO),
+        }
+    }
+}
+
+impl Default for TestLogLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stored on the span of a test, once the test has been registered.
+struct TestId(usize);
+
+impl<S> Layer<S> for TestLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = TestIdVisitor(None);
+        values.record(&mut visitor);
+        if let (Some(test_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(TestId(test_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let target = metadata.target();
+        if *metadata.level() > self.max_level || target == "cogno" || target.starts_with("cogno::") {
+            return;
+        }
+
+        let test_id = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .from_root()
+                .find_map(|span| span.extensions().get::<TestId>().map(|t| t.0))
+        });
+        let test_id = match test_id {
+            Some(test_id) => test_id,
+            None => return,
+        };
+
+        let mut visitor = RecordVisitor::default();
+        event.record(&mut visitor);
+        let record = LogRecord {
+            level: metadata.level().to_string(),
+            target: target.to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+        };
+
+        LOGS.lock()
+            .unwrap()
+            .entry(test_id)
+            .or_default()
+            .push(record);
+    }
+}
+
+/// The events collected for a test, which are removed so that they are only reported once.
+pub fn take(test_id: usize) -> Vec<LogRecord> {
+    LOGS.lock().unwrap().remove(&test_id).unwrap_or_default()
+}
+
+struct TestIdVisitor(Option<usize>);
+
+impl Visit for TestIdVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "test_id" {
+            self.0 = Some(value as usize);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+#[derive(Default)]
+struct RecordVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for RecordVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
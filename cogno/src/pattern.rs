@@ -0,0 +1,65 @@
+use crate::error::CognoError;
+use regex::Regex;
+
+/// A pattern to match names against.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Matches only the exact text.
+    Exact(String),
+    /// Matches any name containing a match for the expression.
+    Search(Regex),
+}
+
+impl Pattern {
+    pub fn exact(text: &str) -> Self {
+        Pattern::Exact(text.to_string())
+    }
+
+    /// A regular expression when prefixed with `re:`, otherwise a glob where `*` matches any
+    /// sequence of characters and `?` matches any single character. Either may match any part of
+    /// the name.
+    pub fn search(text: &str) -> Result<Self, CognoError> {
+        let expression = match text.strip_prefix("re:") {
+            Some(expression) => expression.to_string(),
+            None => glob_to_regex(text),
+        };
+
+        Regex::new(&expression)
+            .map(Pattern::Search)
+            .map_err(|e| CognoError::InvalidPattern(format!("{} - {}", text, e)))
+    }
+
+    /// Like [`Pattern::search`], but the pattern must match the whole name. Text without `*` or `?` which is not
+    /// prefixed with `re:` matches only itself.
+    pub fn whole(text: &str) -> Result<Self, CognoError> {
+        let expression = match text.strip_prefix("re:") {
+            Some(expression) => expression.to_string(),
+            None if text.contains(['*', '?']) => glob_to_regex(text),
+            None => return Ok(Pattern::exact(text)),
+        };
+
+        Regex::new(&format!("^(?:{})$", expression))
+            .map(Pattern::Search)
+            .map_err(|e| CognoError::InvalidPattern(format!("{} - {}", text, e)))
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Pattern::Exact(text) => text == name,
+            Pattern::Search(regex) => regex.is_match(name),
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut expression = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            _ => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    expression
+}
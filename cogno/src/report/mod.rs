@@ -5,4 +5,5 @@ pub mod reporters;
 pub use reporter::Reporter;
 #[cfg(feature = "console")]
 pub use reporters::console::ConsoleReporter;
+pub use reporters::child::ChildReporter;
 pub use reporters::raw::RawReporter;
@@ -1,12 +1,82 @@
 use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::{Display, Formatter};
+use std::panic::PanicHookInfo;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestDef {
     pub name: String,
-    pub spec_id: String,
-    pub panic_info: Option<String>,
+    pub spec_ids: Vec<String>,
+    pub tags: Vec<String>,
+    pub skip_reason: Option<SkipReason>,
+    pub inconclusive_reason: Option<String>,
+    pub panic_info: Option<PanicDef>,
+    /// The error returned by a test function which returns a `Result`.
+    pub returned_error: Option<String>,
+    /// Why the test is expected to fail, for a known non-conformance.
+    pub expected_failure: Option<String>,
+    /// Set when a modifier has disabled the test, which is then not run.
+    pub withdrawn: Option<Withdrawal>,
+    /// How the process running an isolated test ended, if it crashed before the test finished.
+    pub crash: Option<String>,
+    /// What the test wrote to stdout and stderr, when output is captured.
+    pub output: Option<String>,
+    /// The tracing events emitted inside the test.
+    pub logs: Vec<LogRecord>,
     pub completed: bool,
     pub assertions: Vec<AssertionDef>,
+    pub steps: Vec<StepDef>,
+    /// Earlier attempts of a test which was retried, the fields above are the last attempt.
+    pub previous_attempts: Vec<AttemptDef>,
+}
+
+/// The results of one attempt to run a test which was retried.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptDef {
+    pub skip_reason: Option<SkipReason>,
+    pub inconclusive_reason: Option<String>,
+    pub panic_info: Option<PanicDef>,
+    pub returned_error: Option<String>,
+    pub completed: bool,
+    pub assertions: Vec<AssertionDef>,
+    pub steps: Vec<StepDef>,
+}
+
+/// A panic in a test, or in a thread spawned by the test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PanicDef {
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    /// The name of the thread which panicked, test threads are named after their test.
+    pub thread: Option<String>,
+    /// Captured when `RUST_BACKTRACE` is set or with `cargo cogno --backtrace`.
+    pub backtrace: Option<String>,
+}
+
+/// An event emitted with `tracing` while a test ran.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A named part of a test, created with `step!` or `subtest!`, which holds the assertions made inside it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepDef {
+    pub name: String,
+    pub panic_info: Option<PanicDef>,
+    pub completed: bool,
+    pub duration: Option<Duration>,
+    pub assertions: Vec<AssertionDef>,
+    pub steps: Vec<StepDef>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +85,32 @@ pub struct AssertionDef {
     pub kind: AssertionType,
     pub result: bool,
     pub error_message: Option<String>,
+    /// Why the assertion is expected to fail, set by a modifier.
+    pub expected_failure: Option<String>,
+    /// Set when a modifier has disabled the assertion, which then neither passes nor fails.
+    pub withdrawn: Option<Withdrawal>,
+    /// The modifiers which changed the assertion, in the order they were applied.
+    pub modifiers: Vec<AppliedModifier>,
+}
+
+/// What a modifier changed about an assertion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedModifier {
+    /// The file and line of the modifier.
+    pub source: String,
+    pub spec_id: String,
+    pub assertion_type: Option<AssertionType>,
+    pub expect_fail: Option<String>,
+    /// Why the modifier disabled the assertion, if it did.
+    pub disable_reason: Option<String>,
+}
+
+/// A requirement which a modifier has disabled, such as one removed by a later specification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Withdrawal {
+    /// The spec of the modifier which disabled the requirement.
+    pub spec_id: String,
+    pub reason: String,
 }
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
@@ -26,17 +122,118 @@ pub enum AssertionType {
     May,
 }
 
-#[cfg(feature = "console")]
+/// Why a test was not run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SkipReason {
+    /// None of the specs of the test are enabled.
+    SpecDisabled,
+    /// The tags of the test did not match the tag filters.
+    TagFiltered,
+    /// The name of the test did not match the name filter.
+    NameFiltered,
+    /// The test found that it could not run, using `skip!`.
+    PreconditionUnmet(String),
+    /// A test this test depends on did not pass.
+    DependencyFailed(String),
+    /// A test this test depends on was skipped or has not run.
+    DependencyNotRun(String),
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::SpecDisabled => write!(f, "spec disabled"),
+            SkipReason::TagFiltered => write!(f, "tag filtered"),
+            SkipReason::NameFiltered => write!(f, "filtered out"),
+            SkipReason::PreconditionUnmet(reason) => write!(f, "precondition unmet: {}", reason),
+            SkipReason::DependencyFailed(name) => write!(f, "dependency {} did not pass", name),
+            SkipReason::DependencyNotRun(name) => write!(f, "dependency {} did not run", name),
+        }
+    }
+}
+
+impl Display for Withdrawal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "withdrawn from spec {}: {}", self.spec_id, self.reason)
+    }
+}
+
+// The details are only read by reporters which describe each test
+#[cfg_attr(not(feature = "console"), allow(dead_code))]
 #[derive(Debug)]
 pub enum TestOutcome<'a> {
+    Skipped(&'a SkipReason),
+    /// Disabled by a modifier, so the test has no result.
+    Withdrawn(&'a Withdrawal),
     Errored(String),
     AssertionsFailed(Vec<&'a AssertionDef>),
+    Inconclusive(&'a str),
+    /// Failed as expected, with the reason it was expected to.
+    XFail(&'a str),
+    /// Passed when it was expected to fail, with the reason it was expected to.
+    XPass(&'a str),
+    /// Passed, but only after being retried.
+    Flaky,
     Passed,
 }
 
-#[cfg(not(feature = "console"))]
-#[derive(Debug)]
-pub enum TestOutcome {
+/// The overall result of a run against the enabled specs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub enum Verdict {
+    /// No test errored and no _MUST_ or _MUST NOT_ assertion failed.
+    #[default]
+    Conformant,
+    /// Some tests were inconclusive, but otherwise the run was conformant.
+    Inconclusive,
+    /// A test errored or a _MUST_ or _MUST NOT_ assertion failed.
+    NonConformant,
+}
+
+/// Totals of test outcomes for a run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub errored: usize,
+    pub inconclusive: usize,
+    pub xfailed: usize,
+    pub xpassed: usize,
+    pub flaky: usize,
+    pub skipped: usize,
+    pub withdrawn: usize,
+    pub verdict: Verdict,
+}
+
+impl Summary {
+    pub fn add(&mut self, test_def: &TestDef) {
+        self.total += 1;
+        match test_def.get_test_outcome() {
+            TestOutcome::Skipped(_) => self.skipped += 1,
+            TestOutcome::Withdrawn(_) => self.withdrawn += 1,
+            TestOutcome::Errored(_) => {
+                self.errored += 1;
+                self.verdict = Verdict::NonConformant;
+            }
+            TestOutcome::AssertionsFailed(assertions) => {
+                self.failed += 1;
+                if assertions.iter().any(|a| is_required_assertion(&a.kind)) {
+                    self.verdict = Verdict::NonConformant;
+                }
+            }
+            TestOutcome::Inconclusive(_) => {
+                self.inconclusive += 1;
+                if self.verdict == Verdict::Conformant {
+                    self.verdict = Verdict::Inconclusive;
+                }
+            }
+            // Known non-conformances are counted but do not change the verdict
+            TestOutcome::XFail(_) => self.xfailed += 1,
+            TestOutcome::XPass(_) => self.xpassed += 1,
+            TestOutcome::Flaky => self.flaky += 1,
+            TestOutcome::Passed => self.passed += 1,
+        }
+    }
 }
 
 pub fn is_passed_assertion(kind: &AssertionType, result: bool) -> bool {
@@ -49,6 +246,10 @@ pub fn is_passed_assertion(kind: &AssertionType, result: bool) -> bool {
     }
 }
 
+pub fn is_required_assertion(kind: &AssertionType) -> bool {
+    matches!(kind, AssertionType::Must | AssertionType::MustNot)
+}
+
 pub fn is_a_not_assertion(kind: &AssertionType) -> bool {
     match kind {
         AssertionType::MustNot | AssertionType::ShouldNot => true,
@@ -57,32 +258,220 @@ pub fn is_a_not_assertion(kind: &AssertionType) -> bool {
 }
 
 impl TestDef {
-    #[cfg(feature = "console")]
-    pub fn get_test_outcome(&self) -> TestOutcome {
-        if let Some(msg) = self.panic_info.clone() {
-            return TestOutcome::Errored(msg);
+    pub fn get_test_outcome(&self) -> TestOutcome<'_> {
+        let outcome = self.get_actual_outcome();
+
+        match (&self.expected_failure, outcome) {
+            (
+                Some(reason),
+                TestOutcome::Errored(_) | TestOutcome::AssertionsFailed(_) | TestOutcome::XFail(_),
+            ) => TestOutcome::XFail(reason),
+            (Some(reason), TestOutcome::Passed | TestOutcome::XPass(_) | TestOutcome::Flaky) => {
+                TestOutcome::XPass(reason)
+            }
+            (_, outcome) => outcome,
         }
+    }
 
-        if !self.completed {
+    /// The outcome before the test is compared with an expected failure of the whole test.
+    fn get_actual_outcome(&self) -> TestOutcome<'_> {
+        if let Some(reason) = &self.skip_reason {
+            return TestOutcome::Skipped(reason);
+        }
+
+        if let Some(withdrawal) = &self.withdrawn {
+            return TestOutcome::Withdrawn(withdrawal);
+        }
+
+        if let Some(crash) = &self.crash {
+            return TestOutcome::Errored(format!("Crashed: {}", crash));
+        }
+
+        if let Some(panic) = &self.panic_info {
+            return TestOutcome::Errored(panic.to_string());
+        }
+
+        if let Some(error) = &self.returned_error {
+            return TestOutcome::Errored(format!("Returned an error: {}", error));
+        }
+
+        let assertions_outcome = assertions_outcome(&self.assertions, &self.steps);
+
+        // Failed assertions are conclusive, even if the test could not finish
+        let has_failed = matches!(assertions_outcome, TestOutcome::AssertionsFailed(_));
+        if let Some(reason) = &self.inconclusive_reason {
+            if !has_failed {
+                return TestOutcome::Inconclusive(reason);
+            }
+        } else if !self.completed {
             return TestOutcome::Errored("Did not complete".to_string());
+        }
+
+        match assertions_outcome {
+            TestOutcome::Passed if !self.previous_attempts.is_empty() => TestOutcome::Flaky,
+            outcome => outcome,
+        }
+    }
+
+    /// Whether the test failed in a way that retrying could change.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.get_test_outcome(),
+            TestOutcome::Errored(_) | TestOutcome::AssertionsFailed(_)
+        )
+    }
+
+    /// Keep the results of the current attempt and reset the test for another attempt.
+    pub fn begin_attempt(&mut self) {
+        self.previous_attempts.push(AttemptDef {
+            skip_reason: self.skip_reason.take(),
+            inconclusive_reason: self.inconclusive_reason.take(),
+            panic_info: self.panic_info.take(),
+            returned_error: self.returned_error.take(),
+            completed: self.completed,
+            assertions: std::mem::take(&mut self.assertions),
+            steps: std::mem::take(&mut self.steps),
+        });
+        self.completed = false;
+    }
+
+    /// The number of times the test was run.
+    #[cfg_attr(not(feature = "console"), allow(dead_code))]
+    pub fn attempt_count(&self) -> usize {
+        self.previous_attempts.len() + 1
+    }
+}
+
+impl PanicDef {
+    /// Collect the details of a panic from inside a panic hook.
+    pub fn capture(info: &PanicHookInfo) -> Self {
+        let force_backtrace = "true"
+            == std::env::var("COGNO_BACKTRACE")
+                .unwrap_or(String::from("false"))
+                .as_str();
+        let backtrace = if force_backtrace {
+            Backtrace::force_capture()
+        } else {
+            Backtrace::capture()
         };
 
-        let failed_assertions: Vec<&AssertionDef> = self
-            .assertions
-            .iter()
-            .filter(|ta| ta.is_failed_assertion())
-            .collect();
-        if !failed_assertions.is_empty() {
-            return TestOutcome::AssertionsFailed(failed_assertions);
+        PanicDef {
+            message: payload_message(info.payload()),
+            file: info.location().map(|l| l.file().to_string()),
+            line: info.location().map(|l| l.line()),
+            column: info.location().map(|l| l.column()),
+            thread: std::thread::current().name().map(|n| n.to_string()),
+            backtrace: match backtrace.status() {
+                BacktraceStatus::Captured => Some(backtrace.to_string()),
+                _ => None,
+            },
         }
+    }
 
-        TestOutcome::Passed
+    /// The details of a panic which was caught outside of a test, where only its payload is known.
+    pub fn from_payload(payload: &(dyn Any + Send)) -> Self {
+        PanicDef {
+            message: payload_message(payload),
+            file: None,
+            line: None,
+            column: None,
+            thread: std::thread::current().name().map(|n| n.to_string()),
+            backtrace: None,
+        }
     }
 }
 
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "Box<dyn Any>".to_string(),
+    }
+}
+
+impl Display for PanicDef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(thread) = &self.thread {
+            write!(f, "thread '{}' ", thread)?;
+        }
+
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => {
+                write!(f, "panicked at {}:{}:{}:\n{}", file, line, column, self.message)
+            }
+            _ => write!(f, "panicked:\n{}", self.message),
+        }
+    }
+}
+
+impl StepDef {
+    /// The outcome of the step. A panic is only attributed to the step it happened in, the steps around it
+    /// are reported as errored because they did not complete.
+    #[cfg_attr(not(feature = "console"), allow(dead_code))]
+    pub fn get_step_outcome(&self) -> TestOutcome<'_> {
+        if let Some(panic) = &self.panic_info {
+            return TestOutcome::Errored(panic.to_string());
+        }
+
+        let assertions_outcome = assertions_outcome(&self.assertions, &self.steps);
+        if matches!(assertions_outcome, TestOutcome::AssertionsFailed(_)) {
+            return assertions_outcome;
+        }
+
+        if !self.completed {
+            return TestOutcome::Errored("Did not complete".to_string());
+        }
+
+        assertions_outcome
+    }
+}
+
+/// The outcome of the assertions, including those made in steps.
+///
+/// Assertions which are expected to fail do not fail the test. If one passes then it is reported so that the
+/// expectation can be removed. Withdrawn assertions are left out.
+fn assertions_outcome<'a>(assertions: &'a [AssertionDef], steps: &'a [StepDef]) -> TestOutcome<'a> {
+    let all_assertions: Vec<&AssertionDef> = all_assertions(assertions, steps)
+        .into_iter()
+        .filter(|a| a.withdrawn.is_none())
+        .collect();
+
+    let failed_assertions: Vec<&AssertionDef> = all_assertions
+        .iter()
+        .filter(|a| a.is_failed_assertion() && a.expected_failure.is_none())
+        .cloned()
+        .collect();
+    if !failed_assertions.is_empty() {
+        return TestOutcome::AssertionsFailed(failed_assertions);
+    }
+
+    let expected_failure = |failed: bool| {
+        all_assertions
+            .iter()
+            .filter(|a| a.is_failed_assertion() == failed)
+            .find_map(|a| a.expected_failure.as_deref())
+    };
+
+    if let Some(reason) = expected_failure(false) {
+        return TestOutcome::XPass(reason);
+    }
+
+    if let Some(reason) = expected_failure(true) {
+        return TestOutcome::XFail(reason);
+    }
+
+    TestOutcome::Passed
+}
+
+fn all_assertions<'a>(assertions: &'a [AssertionDef], steps: &'a [StepDef]) -> Vec<&'a AssertionDef> {
+    assertions
+        .iter()
+        .chain(steps.iter().flat_map(|s| all_assertions(&s.assertions, &s.steps)))
+        .collect()
+}
+
 impl AssertionDef {
-    #[cfg(feature = "console")]
-    fn is_failed_assertion(&self) -> bool {
+    pub fn is_failed_assertion(&self) -> bool {
         !is_passed_assertion(&self.kind, self.result)
     }
 }
@@ -1,9 +1,15 @@
+use crate::baseline::BaselineComparison;
 use crate::report::model::TestDef;
 use std::fmt::Debug;
 use crate::error::CognoError;
 
 pub trait Reporter: Debug + Send {
+    /// Called when a test is registered, before any of its results are known.
+    fn start(&mut self, _test_def: &TestDef) {}
+
     fn report(&mut self, test_def: &TestDef);
 
+    fn report_baseline(&mut self, comparison: &BaselineComparison);
+
     fn finalize(&self) -> Result<(), CognoError>;
 }
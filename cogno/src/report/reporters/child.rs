@@ -0,0 +1,26 @@
+use crate::baseline::BaselineComparison;
+use crate::error::CognoError;
+use crate::isolation::{send_event, ChildEvent};
+use crate::report::model::TestDef;
+use crate::report::Reporter;
+
+/// Sends results to the parent process when tests are isolated, which reports them with its own reporter.
+#[derive(Debug)]
+pub struct ChildReporter;
+
+impl Reporter for ChildReporter {
+    fn start(&mut self, test_def: &TestDef) {
+        send_event(&ChildEvent::Started(test_def.clone()));
+    }
+
+    fn report(&mut self, test_def: &TestDef) {
+        send_event(&ChildEvent::Finished(test_def.clone()));
+    }
+
+    // The parent process compares the run with the baseline
+    fn report_baseline(&mut self, _comparison: &BaselineComparison) {}
+
+    fn finalize(&self) -> Result<(), CognoError> {
+        Ok(())
+    }
+}
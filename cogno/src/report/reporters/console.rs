@@ -1,74 +1,265 @@
-use crate::report::model::{AssertionType, TestDef, TestOutcome};
+use crate::baseline::BaselineComparison;
+use crate::capture;
+use crate::report::model::{AssertionDef, AssertionType, LogRecord, StepDef, Summary, TestDef, TestOutcome, Verdict};
 use crate::report::reporter::Reporter;
 use colored::Colorize;
 use crate::error::CognoError;
 
+/// Print to the console, which is not captured with the output of the tests.
+macro_rules! console_println {
+    ($($arg:tt)*) => {
+        capture::with_stdout(|out| {
+            let _ = writeln!(out, $($arg)*);
+        })
+    };
+}
+
 #[derive(Debug)]
-pub struct ConsoleReporter {}
+pub struct ConsoleReporter {
+    summary: Summary,
+}
 
 impl ConsoleReporter {
     pub fn new() -> Self {
-        ConsoleReporter {}
+        ConsoleReporter {
+            summary: Summary::default(),
+        }
     }
 }
 
 impl Reporter for ConsoleReporter {
     fn report(&mut self, test_def: &TestDef) {
-        match test_def.get_test_outcome() {
+        self.summary.add(test_def);
+
+        let title = test_title(test_def);
+        let outcome = test_def.get_test_outcome();
+        let is_failed = matches!(outcome, TestOutcome::Errored(_) | TestOutcome::AssertionsFailed(_));
+        match outcome {
+            TestOutcome::Skipped(reason) => {
+                console_println!("{} - {} {}", "\u{25CB}".dimmed(), title, format!("({})", reason).dimmed());
+                return;
+            }
+            TestOutcome::Withdrawn(withdrawal) => {
+                console_println!("{} - {} {}", "\u{2298}".dimmed(), title, format!("({})", withdrawal).dimmed());
+                return;
+            }
             TestOutcome::Passed => {
-                println!("{} - {}", "\u{2713}".green(), test_def.name.bold());
+                console_println!("{} - {}", "\u{2713}".green(), title);
+            }
+            TestOutcome::Flaky => {
+                console_println!(
+                    "{} - {} {}",
+                    "~".yellow(),
+                    title,
+                    format!("(flaky, passed on attempt {})", test_def.attempt_count()).yellow()
+                );
+            }
+            TestOutcome::Inconclusive(reason) => {
+                console_println!("{} - {}\n\t{}", "?".yellow(), title, reason)
+            }
+            TestOutcome::XFail(reason) => {
+                console_println!("{} - {} {}", "x".dimmed(), title, format!("(expected failure: {})", reason).dimmed());
+            }
+            TestOutcome::XPass(reason) => {
+                console_println!("{} - {} {}", "!".yellow(), title, format!("(unexpected pass, expected failure: {})", reason).yellow());
             }
             TestOutcome::Errored(msg) => {
-                println!("{} - {}\n\t{}", "\u{2718}".red(), test_def.name.bold(), msg)
-            }
-            TestOutcome::AssertionsFailed(assertions) => {
-                println!("{} - {}", "\u{2718}", test_def.name.bold());
-                for assertion in assertions {
-                    match assertion.kind {
-                        AssertionType::Must | AssertionType::MustNot => {
-                            println!(
-                                "\t{} - {} {:?}: {}",
-                                "\u{2718}".red(),
-                                assertion.id,
-                                assertion.kind,
-                                assertion
-                                    .error_message
-                                    .as_ref()
-                                    .unwrap_or(&"missing error message".to_string())
-                            )
-                        }
-                        AssertionType::Should | AssertionType::ShouldNot => {
-                            println!(
-                                "\t{} - {} {:?}: {}",
-                                "\u{2718}".blue(),
-                                assertion.id,
-                                assertion.kind,
-                                assertion
-                                    .error_message
-                                    .as_ref()
-                                    .unwrap_or(&"missing error message".to_string())
-                            )
-                        }
-                        AssertionType::May => {
-                            println!(
-                                "\t{} - {} {:?}: {}",
-                                "\u{2718}",
-                                assertion.id,
-                                assertion.kind,
-                                assertion
-                                    .error_message
-                                    .as_ref()
-                                    .unwrap_or(&"missing error message".to_string())
-                            )
-                        }
-                    }
+                console_println!("{} - {}\n\t{}", "\u{2718}".red(), title, msg);
+                if let Some(backtrace) = test_def.panic_info.as_ref().and_then(|p| p.backtrace.as_ref()) {
+                    print_backtrace(backtrace);
                 }
             }
+            TestOutcome::AssertionsFailed(_) => {
+                console_println!("{} - {}", "\u{2718}", title);
+                print_failed_assertions(&test_def.assertions, 1);
+            }
+        }
+
+        print_withdrawn_assertions(&test_def.assertions, 1);
+        print_steps(&test_def.steps, 1);
+
+        // The output of a test is only useful to find out why it failed
+        if is_failed {
+            if let Some(output) = &test_def.output {
+                print_output(output);
+            }
+            print_logs(&test_def.logs);
+        }
+    }
+
+    fn report_baseline(&mut self, comparison: &BaselineComparison) {
+        console_println!("\n{} regressions from the baseline", comparison.regressions.len());
+        for entry in &comparison.regressions {
+            console_println!("\t{} - {}", "\u{2718}".red(), entry);
+        }
+
+        console_println!("{} newly passing since the baseline", comparison.newly_passing.len());
+        for entry in &comparison.newly_passing {
+            console_println!("\t{} - {}", "\u{2713}".green(), entry);
         }
     }
 
     fn finalize(&self) -> Result<(), CognoError> {
-        // Nothing to do
+        console_println!(
+            "\n{} tests: {} passed, {} failed, {} errored, {} inconclusive, {} expected failures, {} unexpected passes, {} flaky, {} skipped, {} withdrawn",
+            self.summary.total,
+            self.summary.passed.to_string().green(),
+            self.summary.failed.to_string().red(),
+            self.summary.errored.to_string().red(),
+            self.summary.inconclusive.to_string().yellow(),
+            self.summary.xfailed.to_string().dimmed(),
+            self.summary.xpassed.to_string().yellow(),
+            self.summary.flaky.to_string().yellow(),
+            self.summary.skipped.to_string().dimmed(),
+            self.summary.withdrawn.to_string().dimmed(),
+        );
+
+        let verdict = match self.summary.verdict {
+            Verdict::Conformant => "conformant".green(),
+            Verdict::Inconclusive => "inconclusive".yellow(),
+            Verdict::NonConformant => "non-conformant".red(),
+        };
+        console_println!("verdict: {}", verdict.bold());
+
         Ok(())
     }
 }
+
+fn test_title(test_def: &TestDef) -> String {
+    let mut title = test_def.name.bold().to_string();
+
+    if !test_def.spec_ids.is_empty() {
+        title.push_str(&format!(" {}", format!("[{}]", test_def.spec_ids.join(", ")).dimmed()));
+    }
+
+    for tag in &test_def.tags {
+        title.push_str(&format!(" {}", format!("#{}", tag).dimmed()));
+    }
+
+    title
+}
+
+fn print_steps(steps: &[StepDef], depth: usize) {
+    let indent = "\t".repeat(depth);
+    for step in steps {
+        let duration = match step.duration {
+            Some(duration) => format!(" ({:?})", duration).dimmed().to_string(),
+            None => String::new(),
+        };
+
+        match step.get_step_outcome() {
+            TestOutcome::Skipped(_) | TestOutcome::Withdrawn(_) | TestOutcome::Flaky | TestOutcome::Passed => {
+                console_println!("{}{} - {}{}", indent, "\u{2713}".green(), step.name, duration);
+            }
+            TestOutcome::XFail(_) => {
+                console_println!("{}{} - {}{}", indent, "x".dimmed(), step.name, duration);
+            }
+            TestOutcome::XPass(_) => {
+                console_println!("{}{} - {}{}", indent, "!".yellow(), step.name, duration);
+            }
+            TestOutcome::Inconclusive(reason) => {
+                console_println!("{}{} - {} {}", indent, "?".yellow(), step.name, format!("({})", reason).dimmed());
+            }
+            TestOutcome::Errored(msg) => {
+                console_println!("{}{} - {}{}\n{}\t{}", indent, "\u{2718}".red(), step.name, duration, indent, msg);
+            }
+            TestOutcome::AssertionsFailed(_) => {
+                console_println!("{}{} - {}{}", indent, "\u{2718}", step.name, duration);
+                print_failed_assertions(&step.assertions, depth + 1);
+            }
+        }
+
+        print_withdrawn_assertions(&step.assertions, depth + 1);
+        print_steps(&step.steps, depth + 1);
+    }
+}
+
+fn print_output(output: &str) {
+    console_println!("\t{}", "output:".dimmed());
+    for line in output.lines() {
+        console_println!("\t\t{}", line);
+    }
+}
+
+fn print_backtrace(backtrace: &str) {
+    console_println!("\t{}", "backtrace:".dimmed());
+    for line in backtrace.lines() {
+        console_println!("\t{}", line.dimmed());
+    }
+}
+
+fn print_logs(logs: &[LogRecord]) {
+    if logs.is_empty() {
+        return;
+    }
+
+    console_println!("\t{}", "log:".dimmed());
+    for record in logs {
+        let fields: Vec<String> = record
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        console_println!(
+            "\t\t{} {}: {} {}",
+            record.level,
+            record.target.dimmed(),
+            record.message,
+            fields.join(" ").dimmed()
+        );
+    }
+}
+
+fn print_failed_assertions(assertions: &[AssertionDef], depth: usize) {
+    let indent = "\t".repeat(depth);
+    for assertion in assertions
+        .iter()
+        .filter(|a| a.is_failed_assertion() && a.expected_failure.is_none() && a.withdrawn.is_none())
+    {
+        let symbol = match assertion.kind {
+            AssertionType::Must | AssertionType::MustNot => "\u{2718}".red(),
+            AssertionType::Should | AssertionType::ShouldNot => "\u{2718}".blue(),
+            AssertionType::May => "\u{2718}".normal(),
+        };
+
+        console_println!(
+            "{}{} - {} {:?}: {}{}",
+            indent,
+            symbol,
+            assertion.id,
+            assertion.kind,
+            assertion
+                .error_message
+                .as_ref()
+                .unwrap_or(&"missing error message".to_string()),
+            modified_by(assertion)
+        )
+    }
+}
+
+/// Where the modifiers which changed an assertion were written, so that the reader can tell why it changed.
+fn modified_by(assertion: &AssertionDef) -> String {
+    if assertion.modifiers.is_empty() {
+        return String::new();
+    }
+
+    let sources: Vec<&str> = assertion.modifiers.iter().map(|m| m.source.as_str()).collect();
+    format!(" {}", format!("(modified by {})", sources.join(", ")).dimmed())
+}
+
+fn print_withdrawn_assertions(assertions: &[AssertionDef], depth: usize) {
+    let indent = "\t".repeat(depth);
+    for assertion in assertions {
+        if let Some(withdrawal) = &assertion.withdrawn {
+            console_println!(
+                "{}{} - {} {:?} {}{}",
+                indent,
+                "\u{2298}".dimmed(),
+                assertion.id,
+                assertion.kind,
+                format!("({})", withdrawal).dimmed(),
+                modified_by(assertion)
+            );
+        }
+    }
+}
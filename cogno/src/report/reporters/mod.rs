@@ -1,3 +1,4 @@
+pub mod child;
 #[cfg(feature = "console")]
 pub mod console;
 pub mod raw;
@@ -1,29 +1,42 @@
 use std::fs::File;
 use std::io::Write;
+use crate::baseline::BaselineComparison;
 use crate::error::CognoError;
-use crate::report::model::TestDef;
+use crate::report::model::{Summary, TestDef};
 use crate::report::Reporter;
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct RawReporter {
+    summary: Summary,
+    #[serde(rename = "tests")]
     test_defs: Vec<TestDef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline: Option<BaselineComparison>,
 }
 
 impl RawReporter {
     pub fn new() -> Self {
         RawReporter {
+            summary: Summary::default(),
             test_defs: Vec::new(),
+            baseline: None,
         }
     }
 }
 
 impl Reporter for RawReporter {
     fn report(&mut self, test_def: &TestDef) {
+        self.summary.add(test_def);
         self.test_defs.push(test_def.clone());
     }
 
+    fn report_baseline(&mut self, comparison: &BaselineComparison) {
+        self.baseline = Some(comparison.clone());
+    }
+
     fn finalize(&self) -> Result<(), CognoError> {
-        let str = serde_json::to_string(&self.test_defs)?;
+        let str = serde_json::to_string(self)?;
         let mut f = File::create("cogno-report.json")?;
         f.write(str.as_bytes())?;
 
@@ -0,0 +1,336 @@
+use crate::report::model::{PanicDef, SkipReason};
+use crate::TestController;
+use std::cell::Cell;
+use std::fmt::Display;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::thread::{Scope, ScopedJoinHandle};
+
+thread_local! {
+    /// The test running on this thread, used to attribute assertions when tests run at the same time.
+    static CURRENT_TEST: Cell<Option<usize>> = const { Cell::new(None) };
+    /// The index of the test function running on this scheduler thread.
+    static CURRENT_FUNCTION: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+pub(crate) fn current_test() -> Option<usize> {
+    CURRENT_TEST.with(|c| c.get())
+}
+
+pub(crate) fn current_function() -> Option<usize> {
+    CURRENT_FUNCTION.with(|c| c.get())
+}
+
+pub(crate) fn set_current_function(index: Option<usize>) {
+    CURRENT_FUNCTION.with(|c| c.set(index));
+}
+
+/// The metadata of a test, which decides whether it runs and is included in the report.
+#[derive(Debug, Clone, Default)]
+pub struct TestMeta {
+    pub name: String,
+    /// The test function which declared the test, which is what other tests depend on.
+    pub function: String,
+    pub spec_ids: Vec<String>,
+    pub tags: Vec<String>,
+    /// The test functions which must pass before this test can run.
+    pub depends_on: Vec<String>,
+    /// Why the test is expected to fail, for a known non-conformance.
+    pub expect_fail: Option<String>,
+    /// How many times to retry the test if it fails, instead of the number set for the run.
+    pub retries: Option<u32>,
+}
+
+impl TestMeta {
+    pub fn new(name: &str, spec_ids: &[&str], tags: &[&str]) -> Self {
+        TestMeta {
+            name: name.to_string(),
+            function: name.to_string(),
+            spec_ids: spec_ids.iter().map(|s| s.to_string()).collect(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            depends_on: Vec::new(),
+            expect_fail: None,
+            retries: None,
+        }
+    }
+}
+
+/// Used by the test harness. Not for direct use, see [`TestFactory`] to create tests at runtime.
+///
+/// Runs a single test. The test is skipped if none of its specs are enabled, its tags or name are
+/// filtered out or one of its dependencies did not pass, and reported with the reason. Otherwise it
+/// is registered with the controller and run on its own thread so that a panic can be caught and
+/// reported. A test which fails is run again while it has retries left.
+pub fn run_test<F, R>(controller: &Arc<Mutex<TestController>>, meta: TestMeta, test: F)
+where
+    F: Fn() -> R + Send,
+    R: TestReturn,
+{
+    let name = meta.name.as_str();
+    let span = tracing::span!(
+        tracing::Level::INFO,
+        "cogno_test",
+        name,
+        test_id = tracing::field::Empty
+    );
+    let _enter = span.enter();
+    tracing::event!(tracing::Level::INFO, "enter");
+
+    let (test_id, retries) = {
+        let mut controller_handle = controller.lock().unwrap();
+        // Another child process runs the test when tests are isolated by spec
+        if !controller_handle.is_in_isolated_spec(&meta.spec_ids) {
+            return;
+        }
+
+        if !controller_handle.is_any_spec_enabled(&meta.spec_ids) {
+            tracing::event!(tracing::Level::INFO, "skipped");
+            controller_handle.skip(&meta, SkipReason::SpecDisabled);
+            return;
+        }
+
+        if !controller_handle.is_tag_enabled(&meta.tags) {
+            tracing::event!(tracing::Level::INFO, "skipped by tag");
+            controller_handle.skip(&meta, SkipReason::TagFiltered);
+            return;
+        }
+
+        if !controller_handle.is_name_enabled(name) {
+            tracing::event!(tracing::Level::INFO, "filtered out");
+            controller_handle.skip(&meta, SkipReason::NameFiltered);
+            return;
+        }
+
+        if let Some(withdrawal) = controller_handle.test_withdrawal(&meta) {
+            tracing::event!(tracing::Level::INFO, "withdrawn");
+            controller_handle.withdraw(&meta, withdrawal);
+            return;
+        }
+
+        if let Some(reason) = controller_handle.dependency_skip_reason(&meta.depends_on) {
+            tracing::event!(tracing::Level::INFO, "skipped by dependency");
+            controller_handle.skip(&meta, reason);
+            return;
+        }
+
+        let test_id = controller_handle.register(&meta);
+        span.record("test_id", test_id);
+        (test_id, controller_handle.retries_for(&meta))
+    };
+
+    let test_span = span.clone();
+    // The test state is discarded if the test panics, so it is safe to assume unwind safety
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn_scoped(scope, move || {
+                CURRENT_TEST.with(|c| c.set(Some(test_id)));
+                // Events emitted by the test are collected for the report through its span
+                let _enter = test_span.enter();
+                for attempt in 0..=retries {
+                    if attempt > 0 {
+                        tracing::event!(tracing::Level::INFO, "retrying");
+                        controller.lock().unwrap().retry(test_id);
+                    }
+
+                    let result =
+                        std::panic::catch_unwind(AssertUnwindSafe(|| test().into_error()));
+                    let mut controller_handle = controller.lock().unwrap();
+                    record_attempt(&mut controller_handle, test_id, result);
+                    if !controller_handle.is_retryable(test_id) {
+                        break;
+                    }
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    });
+
+    tracing::event!(tracing::Level::INFO, "exit");
+    controller.lock().unwrap().finish(test_id, &meta.function);
+}
+
+fn record_attempt(
+    controller_handle: &mut TestController,
+    test_id: usize,
+    result: std::thread::Result<Option<String>>,
+) {
+    match result {
+        Ok(Some(error)) => controller_handle.set_returned_error(test_id, error),
+        Ok(None) => controller_handle.complete(test_id),
+        Err(payload) => match payload.downcast_ref::<TestAbort>() {
+            Some(TestAbort::Skip(reason)) => {
+                controller_handle.skip_test(test_id, SkipReason::PreconditionUnmet(reason.clone()));
+            }
+            Some(TestAbort::Inconclusive(reason)) => {
+                controller_handle.set_inconclusive(test_id, reason.clone());
+            }
+            None => {}
+        },
+    }
+}
+
+/// A value which a test can return, either `()` or a `Result<(), E>` where `E` implements `Display`.
+pub trait TestReturn {
+    /// The error returned by the test, if any.
+    fn into_error(self) -> Option<String>;
+}
+
+impl TestReturn for () {
+    fn into_error(self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: Display> TestReturn for Result<(), E> {
+    fn into_error(self) -> Option<String> {
+        self.err().map(|e| e.to_string())
+    }
+}
+
+/// Spawn a thread which belongs to the current test.
+///
+/// A panic on the thread is recorded against the test which spawned it, even when other tests are running at
+/// the same time. Tracing events from the thread are added to the log of the test.
+///
+/// ```
+/// let server = cogno::spawn(move || serve_one_query(socket));
+/// ```
+pub fn spawn<F, T>(f: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(in_current_test(f))
+}
+
+/// Spawn a scoped thread which belongs to the current test, see [`spawn`].
+///
+/// A scoped thread can also make assertions, which are recorded against the test.
+///
+/// ```
+/// std::thread::scope(|scope| {
+///     cogno::spawn_scoped(scope, || {
+///         must_eq!("rfc_1035_concurrent_query", true, query_over_udp().is_ok());
+///     });
+/// });
+/// ```
+pub fn spawn_scoped<'scope, 'env, F, T>(
+    scope: &'scope Scope<'scope, 'env>,
+    f: F,
+) -> ScopedJoinHandle<'scope, T>
+where
+    F: FnOnce() -> T + Send + 'scope,
+    T: Send + 'scope,
+{
+    scope.spawn(in_current_test(f))
+}
+
+/// Wrap a function to run it as part of the current test on another thread.
+fn in_current_test<F: FnOnce() -> T, T>(f: F) -> impl FnOnce() -> T {
+    let test_id = current_test();
+    let span = tracing::Span::current();
+    move || {
+        CURRENT_TEST.with(|c| c.set(test_id));
+        let _enter = span.enter();
+        f()
+    }
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// A panic payload used to stop a test early without it being reported as a panic.
+#[derive(Debug)]
+pub enum TestAbort {
+    Skip(String),
+    Inconclusive(String),
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Runs the body of a function marked with `#[cogno_test_factory]`. A panic in the factory itself, rather than in
+/// one of the tests it registers, is reported as an errored test named after the function so that the rest of the
+/// suite still runs and tests which depend on the factory are skipped.
+pub fn run_factory<F>(controller: &Arc<Mutex<TestController>>, function: &str, factory: F)
+where
+    F: FnOnce(&mut TestFactory),
+{
+    let mut test_factory = TestFactory::new(controller, function);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| factory(&mut test_factory)));
+    if let Err(payload) = result {
+        tracing::event!(tracing::Level::INFO, "test factory panicked");
+        let mut controller_handle = controller.lock().unwrap();
+        // The factory has no spec, so only the child process for tests without an enabled spec reports it
+        if controller_handle.is_in_isolated_spec(&[]) {
+            let meta = TestMeta::new(function, &[], &[]);
+            controller_handle.function_panicked(&meta, PanicDef::from_payload(&*payload));
+        }
+    }
+}
+
+/// Create tests while the suite is running.
+///
+/// A test factory is available inside a function marked with `#[cogno_test_factory]`. It is useful
+/// when the tests to run are only known at runtime, such as one test per file in a directory.
+///
+/// ```
+/// #[cogno_test_factory]
+/// fn zone_files(factory: &mut TestFactory) {
+///     for zone in ["example.com", "example.org"] {
+///         factory.register(zone, &["rfc-1035"], || {
+///             must_eq!("rfc_1035_zone_loads", true, load_zone(zone).is_ok());
+///         });
+///     }
+/// }
+/// ```
+///
+/// Each registered test runs in the same way as a test marked with `#[cogno_test]` and is reported
+/// on its own. The closure may be called more than once if the test is retried.
+///
+/// Other tests can depend on the factory by its function name, in which case every registered test
+/// must pass.
+pub struct TestFactory<'a> {
+    controller: &'a Arc<Mutex<TestController>>,
+    function: String,
+}
+
+impl<'a> TestFactory<'a> {
+    /// Used by the test harness. Not for direct use.
+    pub fn new(controller: &'a Arc<Mutex<TestController>>, function: &str) -> Self {
+        TestFactory {
+            controller,
+            function: function.to_string(),
+        }
+    }
+
+    /// Register a test with a name and spec identifiers and run it immediately.
+    ///
+    /// The test is skipped if none of the specs are enabled, just like a test marked with `#[cogno_test]`.
+    pub fn register<F, R>(&mut self, name: &str, spec_ids: &[&str], test: F)
+    where
+        F: Fn() -> R + Send,
+        R: TestReturn,
+    {
+        self.register_with(TestMeta::new(name, spec_ids, &[]), test);
+    }
+
+    /// Register a test with all of its metadata, such as tags, and run it immediately.
+    ///
+    /// ```
+    /// factory.register_with(TestMeta::new(zone, &["rfc-1035"], &["authoritative"]), || {
+    ///     must_eq!("rfc_1035_zone_loads", true, load_zone(zone).is_ok());
+    /// });
+    /// ```
+    pub fn register_with<F, R>(&mut self, meta: TestMeta, test: F)
+    where
+        F: Fn() -> R + Send,
+        R: TestReturn,
+    {
+        let meta = TestMeta {
+            function: self.function.clone(),
+            ..meta
+        };
+        run_test(self.controller, meta, test);
+    }
+}
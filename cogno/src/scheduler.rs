@@ -0,0 +1,149 @@
+use crate::isolation::{self, Isolation};
+use crate::runner::set_current_function;
+use crate::TestController;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Used by the test harness. Not for direct use.
+///
+/// A function marked with `#[cogno_test]` or `#[cogno_test_factory]`, as listed in the manifest.
+pub struct TestFunction {
+    pub name: &'static str,
+    /// The test functions which must finish before this one starts.
+    pub depends_on: &'static [&'static str],
+    /// Functions in the same serial group never run at the same time.
+    pub serial: Option<&'static str>,
+    pub run: fn(&mut Arc<Mutex<TestController>>),
+}
+
+/// The test functions which have started, and what they are still holding up.
+struct Schedule<'a> {
+    started: Vec<bool>,
+    /// The number of functions with each name which have not finished.
+    unfinished: HashMap<&'a str, usize>,
+    busy_groups: HashSet<&'a str>,
+}
+
+impl<'a> Schedule<'a> {
+    fn new(functions: &'a [TestFunction]) -> Self {
+        let mut unfinished = HashMap::new();
+        for function in functions {
+            *unfinished.entry(function.name).or_insert(0) += 1;
+        }
+
+        Schedule {
+            started: vec![false; functions.len()],
+            unfinished,
+            busy_groups: HashSet::new(),
+        }
+    }
+
+    /// The first function which has not started and can start now.
+    fn next_ready(&self, functions: &[TestFunction]) -> Option<usize> {
+        functions.iter().enumerate().position(|(index, function)| {
+            !self.started[index]
+                && function
+                    .depends_on
+                    .iter()
+                    .all(|d| self.unfinished.get(d).copied().unwrap_or(0) == 0)
+                && !matches!(function.serial, Some(group) if self.busy_groups.contains(group))
+        })
+    }
+
+    fn start(&mut self, index: usize, function: &'a TestFunction) {
+        self.started[index] = true;
+        if let Some(group) = function.serial {
+            self.busy_groups.insert(group);
+        }
+    }
+
+    fn finish(&mut self, function: &'a TestFunction) {
+        if let Some(count) = self.unfinished.get_mut(function.name) {
+            *count -= 1;
+        }
+        if let Some(group) = function.serial {
+            self.busy_groups.remove(group);
+        }
+    }
+
+    fn is_all_started(&self) -> bool {
+        self.started.iter().all(|s| *s)
+    }
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Runs the test functions on as many threads as the controller allows. Functions are started in the order
+/// given, once the functions they depend on have finished and no other function in their serial group is
+/// running. With one job this runs the functions one after another.
+///
+/// When tests are isolated, the functions run in child processes instead, which are copies of this process
+/// that each run one function or the tests of one spec.
+pub fn run_test_functions(controller: &Arc<Mutex<TestController>>, functions: &[TestFunction]) {
+    let isolation = controller.lock().unwrap().isolation().clone();
+    match isolation {
+        Isolation::Shared | Isolation::ChildSpec(_) => {
+            schedule(controller, functions, |controller, _, function| {
+                (function.run)(&mut controller.clone())
+            })
+        }
+        Isolation::PerTest => schedule(controller, functions, isolation::run_function_in_child),
+        Isolation::PerSpec => isolation::run_specs_in_children(controller),
+        // Reported as soon as the tests finish, the parent process puts them in order
+        Isolation::ChildFunction(index) => (functions[index].run)(&mut controller.clone()),
+    }
+}
+
+type RunFunction = fn(&Arc<Mutex<TestController>>, usize, &TestFunction);
+
+fn schedule(
+    controller: &Arc<Mutex<TestController>>,
+    functions: &[TestFunction],
+    run: RunFunction,
+) {
+    let jobs = controller.lock().unwrap().jobs();
+    let schedule = Mutex::new(Schedule::new(functions));
+    let changed = Condvar::new();
+
+    std::thread::scope(|scope| {
+        for job in 0..jobs {
+            std::thread::Builder::new()
+                .name(format!("cogno-job-{}", job))
+                .spawn_scoped(scope, || {
+                    run_ready_functions(controller, functions, run, &schedule, &changed)
+                })
+                .unwrap();
+        }
+    });
+}
+
+fn run_ready_functions<'a>(
+    controller: &Arc<Mutex<TestController>>,
+    functions: &'a [TestFunction],
+    run: RunFunction,
+    schedule: &Mutex<Schedule<'a>>,
+    changed: &Condvar,
+) {
+    let mut schedule_handle = schedule.lock().unwrap();
+    loop {
+        if let Some(index) = schedule_handle.next_ready(functions) {
+            let function = &functions[index];
+            schedule_handle.start(index, function);
+            drop(schedule_handle);
+
+            tracing::event!(tracing::Level::INFO, "starting {}", function.name);
+            set_current_function(Some(index));
+            run(controller, index, function);
+            set_current_function(None);
+            controller.lock().unwrap().function_done(index);
+
+            schedule_handle = schedule.lock().unwrap();
+            schedule_handle.finish(function);
+            changed.notify_all();
+        } else if schedule_handle.is_all_started() {
+            return;
+        } else {
+            schedule_handle = changed.wait(schedule_handle).unwrap();
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use super::{
+    is_layered, line_of, modifier_paths, AssertionModifier, ModifierFile, ModifierId, SpecModifier, SpecModifiers,
+};
+use crate::error::CognoError;
+use crate::inventory::{function_name, Inventory, InventoryTest};
+use crate::isolation::Isolation;
+use crate::load_list;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    /// A modifier which does not apply to any discovered test, which is usually a typo.
+    Warning,
+    /// A modifier file which cannot be used, or modifiers which would stop the run.
+    Error,
+}
+
+#[derive(Debug)]
+struct Problem {
+    severity: Severity,
+    location: String,
+    message: String,
+}
+
+/// An assertion modifier, with the file it was loaded from and the spec and test it applies to.
+struct Entry<'a> {
+    file: &'a ModifierFile,
+    spec_modifier: &'a SpecModifier,
+    test_id: &'a ModifierId,
+    modifier: &'a AssertionModifier,
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Check the modifier files given with `--modifier` against the tests discovered in the suite, returning the exit
+/// code when the suite should stop rather than run its tests.
+///
+/// With `cargo cogno check-modifiers` every problem is printed and the suite stops. Before a run, references to
+/// unknown specs, tests and assertions are printed as warnings and the run only stops if the modifier files cannot
+/// be loaded, or if they conflict for a test that would run.
+pub fn check_modifiers(inventory: &Inventory) -> Result<Option<i32>, CognoError> {
+    let full_check = "true"
+        == std::env::var("COGNO_CHECK_MODIFIERS")
+            .unwrap_or(String::from("false"))
+            .as_str();
+
+    // Child processes run with the same modifiers, which the parent has already checked
+    if !full_check && Isolation::load()?.is_child() {
+        return Ok(None);
+    }
+
+    let paths = modifier_paths();
+    if paths.is_empty() {
+        if full_check {
+            println!("no modifier files to check, give them with --modifier");
+            return Ok(Some(0));
+        }
+        return Ok(None);
+    }
+
+    let mut problems = Vec::new();
+    let files = load_files(&paths, &mut problems);
+    for file in &files {
+        check_references(file, inventory, &mut problems);
+    }
+
+    let enabled_specs = load_list("COGNO_SPECS");
+    check_conflicts(
+        &files,
+        inventory,
+        |test| full_check || test.spec_ids.iter().any(|s| enabled_specs.contains(s)),
+        |spec_modifier| full_check || spec_modifier.is_active(&enabled_specs),
+        is_layered(),
+        &mut problems,
+    );
+
+    let errors = problems.iter().filter(|p| p.severity == Severity::Error).count();
+    let warnings = problems.len() - errors;
+
+    if full_check {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        println!(
+            "checked {} modifier files: {} errors, {} warnings",
+            paths.len(),
+            errors,
+            warnings
+        );
+        return Ok(Some(if problems.is_empty() { 0 } else { 1 }));
+    }
+
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+    if errors > 0 {
+        eprintln!("not running the tests, fix the modifier files or check them with `cargo cogno check-modifiers`");
+        return Ok(Some(1));
+    }
+
+    Ok(None)
+}
+
+fn load_files(paths: &[String], problems: &mut Vec<Problem>) -> Vec<ModifierFile> {
+    let mut files = Vec::new();
+    for path in paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                problems.push(Problem::error(path.clone(), format!("could not read the file - {}", e)));
+                continue;
+            }
+        };
+
+        match toml::from_str::<SpecModifiers>(&source) {
+            Ok(modifiers) => {
+                let file = ModifierFile {
+                    path: path.clone(),
+                    source,
+                    modifiers,
+                };
+                for id in file.modifiers.missing_reasons() {
+                    problems.push(Problem::error(
+                        file.location(id),
+                        format!("`{}` is disabled without a reason", id.get_ref()),
+                    ));
+                }
+                files.push(file);
+            }
+            Err(e) => {
+                let location = match e.span() {
+                    Some(span) => format!("{}:{}", path, line_of(&source, span.start)),
+                    None => path.clone(),
+                };
+                problems.push(Problem::error(location, e.message().to_string()));
+            }
+        }
+    }
+
+    files
+}
+
+/// Modifiers only apply when the spec, test and assertion ids all match, so a typo in any of them means the
+/// modifier silently does nothing.
+fn check_references(file: &ModifierFile, inventory: &Inventory, problems: &mut Vec<Problem>) {
+    for spec_modifier in &file.modifiers.spec_modifiers {
+        let spec_id = spec_modifier.spec_id.get_ref();
+        if !inventory.tests().iter().any(|t| t.spec_ids.contains(spec_id)) {
+            problems.push(Problem::warning(
+                file.location(&spec_modifier.spec_id),
+                format!("no tests for spec `{}`", spec_id),
+            ));
+            continue;
+        }
+
+        for condition in spec_modifier.when_specs.iter().chain(&spec_modifier.unless_specs) {
+            if !inventory.tests().iter().any(|t| t.spec_ids.contains(condition.get_ref())) {
+                problems.push(Problem::warning(
+                    file.location(condition),
+                    format!("no tests for spec `{}`", condition.get_ref()),
+                ));
+            }
+        }
+
+        if !spec_modifier.can_be_active_with(spec_modifier) {
+            problems.push(Problem::warning(
+                file.location(&spec_modifier.spec_id),
+                "a spec is in both `when_specs` and `unless_specs`, so the modifier never applies".to_string(),
+            ));
+            continue;
+        }
+
+        for test_modifier in &spec_modifier.test_modifiers {
+            let test_id = &test_modifier.test_id;
+            let named: Vec<&InventoryTest> = inventory
+                .tests()
+                .iter()
+                .filter(|t| !t.factory && applies_to(test_id, t))
+                .collect();
+            // The tests registered by a factory are only known at runtime, so the modifier may be for one of them
+            if named.is_empty() && inventory.tests().iter().any(|t| t.factory) {
+                continue;
+            }
+            if named.is_empty() {
+                let message = match test_id.is_pattern() {
+                    true => format!("no test matches `{}`", test_id.as_str()),
+                    false => format!("no test named `{}`", test_id.as_str()),
+                };
+                problems.push(Problem::warning(file.location(&test_id.id), message));
+                continue;
+            }
+
+            let tests: Vec<&InventoryTest> = named
+                .into_iter()
+                .filter(|t| t.spec_ids.contains(spec_id))
+                .collect();
+            if tests.is_empty() {
+                problems.push(Problem::warning(
+                    file.location(&test_id.id),
+                    format!("`{}` is not a test of spec `{}`", test_id.as_str(), spec_id),
+                ));
+                continue;
+            }
+
+            for assertion_modifier in &test_modifier.assertion_modifiers {
+                let assertion_id = &assertion_modifier.assertion_id;
+                let found = tests
+                    .iter()
+                    .flat_map(|t| &t.assertion_ids)
+                    .any(|id| assertion_id.is_match(id));
+                if !found {
+                    let message = match assertion_id.is_pattern() {
+                        true => format!("no assertion of `{}` matches `{}`", test_id.as_str(), assertion_id.as_str()),
+                        false => format!("`{}` has no assertion `{}`", test_id.as_str(), assertion_id.as_str()),
+                    };
+                    problems.push(Problem::warning(file.location(&assertion_id.id), message));
+                }
+            }
+        }
+    }
+}
+
+/// Find assertions which are changed in different ways by modifiers for the specs of the same test, which fail
+/// the test run with `CognoError::ConflictingModifiers` when the assertion is made. Only the most specific
+/// modifiers for an assertion apply, so a pattern does not conflict with a more specific id.
+fn check_conflicts(
+    files: &[ModifierFile],
+    inventory: &Inventory,
+    would_run: impl Fn(&InventoryTest) -> bool,
+    is_active: impl Fn(&SpecModifier) -> bool,
+    layered: bool,
+    problems: &mut Vec<Problem>,
+) {
+    let entries: Vec<Entry> = files
+        .iter()
+        .flat_map(|file| {
+            file.modifiers.spec_modifiers.iter().flat_map(move |sm| {
+                sm.test_modifiers.iter().flat_map(move |tm| {
+                    tm.assertion_modifiers.iter().map(move |am| Entry {
+                        file,
+                        spec_modifier: sm,
+                        test_id: &tm.test_id,
+                        modifier: am,
+                    })
+                })
+            })
+        })
+        .filter(|e| is_active(e.spec_modifier))
+        .collect();
+
+    for test in inventory.tests().iter().filter(|t| !t.factory && would_run(t)) {
+        let test_entries: Vec<&Entry> = entries
+            .iter()
+            .filter(|e| {
+                test.spec_ids.iter().any(|s| s == e.spec_modifier.spec_id.get_ref()) && applies_to(e.test_id, test)
+            })
+            .collect();
+
+        // Assertions made outside the body of the test are only known from the modifiers which name them
+        let mut assertion_ids: Vec<&str> = test.assertion_ids.iter().map(|id| id.as_str()).collect();
+        for entry in &test_entries {
+            let id = entry.modifier.assertion_id.as_str();
+            if !entry.modifier.assertion_id.is_pattern() && !assertion_ids.contains(&id) {
+                assertion_ids.push(id);
+            }
+        }
+
+        for assertion_id in assertion_ids {
+            let matching: Vec<&Entry> = test_entries
+                .iter()
+                .filter(|e| e.modifier.assertion_id.is_match(assertion_id))
+                .copied()
+                .collect();
+
+            // Layered files override each other, so only modifiers in the same file can conflict
+            let layers: Vec<Vec<&Entry>> = match layered {
+                true => files
+                    .iter()
+                    .map(|file| {
+                        matching
+                            .iter()
+                            .filter(|e| std::ptr::eq(e.file, file))
+                            .copied()
+                            .collect()
+                    })
+                    .collect(),
+                false => vec![matching],
+            };
+
+            for layer in layers {
+                if let Some(problem) = find_conflict(layer, assertion_id, &test.name) {
+                    problems.push(problem);
+                }
+            }
+        }
+    }
+}
+
+/// The most specific modifiers of an assertion conflict if they change it in different ways.
+fn find_conflict(matching: Vec<&Entry>, assertion_id: &str, test_name: &str) -> Option<Problem> {
+    let precedence = |e: &Entry| (e.modifier.assertion_id.specificity(), e.test_id.specificity());
+    let highest = matching.iter().map(|e| precedence(e)).max();
+    let matching: Vec<&Entry> = matching
+        .into_iter()
+        .filter(|e| Some(precedence(e)) == highest)
+        .collect();
+
+    // The same change made in more than one place is not a conflict, nor are modifiers whose spec conditions
+    // mean that they never apply together
+    let conflicts = |a: &Entry, b: &Entry| {
+        !a.modifier.has_same_effect(b.modifier) && a.spec_modifier.can_be_active_with(b.spec_modifier)
+    };
+    let first = matching.iter().find(|a| matching.iter().any(|b| conflicts(a, b)))?;
+
+    let others: Vec<String> = matching
+        .iter()
+        .filter(|b| conflicts(first, b))
+        .map(|e| e.file.location(&e.modifier.assertion_id.id))
+        .collect();
+    Some(Problem::error(
+        first.file.location(&first.modifier.assertion_id.id),
+        format!(
+            "conflicting modifiers for assertion `{}` of test `{}`, also changed at {}",
+            assertion_id,
+            test_name,
+            others.join(", ")
+        ),
+    ))
+}
+
+/// Whether a modifier's test id names a discovered test, using the same rule as [`ModifierId::is_test_match`] when
+/// the tests run. An exact id may name one vector of a test, or all of them by the name of the test function.
+fn applies_to(test_id: &ModifierId, test: &InventoryTest) -> bool {
+    match test_id.is_pattern() {
+        true => test_id.is_match(&test.name),
+        false => function_name(test_id.as_str()) == test.name,
+    }
+}
+
+impl Problem {
+    fn warning(location: String, message: String) -> Self {
+        Problem {
+            severity: Severity::Warning,
+            location,
+            message,
+        }
+    }
+
+    fn error(location: String, message: String) -> Self {
+        Problem {
+            severity: Severity::Error,
+            location,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
@@ -1,37 +1,402 @@
 use crate::error::CognoError;
+use crate::inventory::function_name;
+use crate::pattern::Pattern;
 use crate::report::model::AssertionType;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
+use toml::Spanned;
+
+mod check;
+
+pub use check::check_modifiers;
 
 #[derive(Debug, Deserialize)]
 pub struct SpecModifiers {
     pub spec_modifiers: Vec<SpecModifier>,
 }
 
+/// The ids in a modifier keep where they were written, so that mistakes can be reported with a line number.
 #[derive(Debug, Deserialize)]
 pub struct SpecModifier {
-    pub spec_id: String,
+    pub spec_id: Spanned<String>,
+    /// The modifier only applies when all of these specs are enabled.
+    #[serde(default)]
+    pub when_specs: Vec<Spanned<String>>,
+    /// The modifier does not apply when any of these specs are enabled.
+    #[serde(default)]
+    pub unless_specs: Vec<Spanned<String>>,
     pub test_modifiers: Vec<TestModifier>,
 }
 
+impl SpecModifier {
+    /// Whether the conditions of the modifier hold for the enabled specs.
+    pub fn is_active(&self, enabled_specs: &HashSet<String>) -> bool {
+        self.when_specs.iter().all(|s| enabled_specs.contains(s.get_ref()))
+            && !self.unless_specs.iter().any(|s| enabled_specs.contains(s.get_ref()))
+    }
+
+    /// Whether some choice of enabled specs makes both modifiers apply.
+    pub fn can_be_active_with(&self, other: &SpecModifier) -> bool {
+        let excludes = |a: &SpecModifier, b: &SpecModifier| {
+            a.when_specs.iter().any(|w| b.unless_specs.iter().any(|u| w.get_ref() == u.get_ref()))
+        };
+        !excludes(self, other) && !excludes(other, self) && !excludes(self, self) && !excludes(other, other)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TestModifier {
-    pub test_id: String,
+    pub test_id: ModifierId,
+    pub action: Option<ModifierAction>,
+    /// Why the action was taken, which is required to disable the test.
+    pub reason: Option<String>,
+    #[serde(default)]
     pub assertion_modifiers: Vec<AssertionModifier>,
 }
 
-#[derive(Debug, Hash, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct AssertionModifier {
-    pub assertion_id: String,
-    pub assertion_type: AssertionType,
+    pub assertion_id: ModifierId,
+    pub assertion_type: Option<AssertionType>,
+    /// Why the assertion is expected to fail, for a known non-conformance.
+    pub expect_fail: Option<String>,
+    pub action: Option<ModifierAction>,
+    /// Why the action was taken, which is required to disable the assertion.
+    pub reason: Option<String>,
+}
+
+impl AssertionModifier {
+    /// Whether two modifiers change an assertion in the same way, whatever id they were written for.
+    pub fn has_same_effect(&self, other: &AssertionModifier) -> bool {
+        self.assertion_type == other.assertion_type
+            && self.expect_fail == other.expect_fail
+            && self.action == other.action
+    }
+}
+
+/// A test or assertion id in a modifier. It may be a glob such as `rfc_1234_sec_8.*`, or a regular expression
+/// prefixed with `re:`, to modify every test or assertion whose whole id matches.
+#[derive(Debug)]
+pub struct ModifierId {
+    pub id: Spanned<String>,
+    pattern: Pattern,
+    specificity: Specificity,
+}
+
+/// How precisely a modifier id names what it matches. When several modifiers match, the most specific ones
+/// take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Specificity {
+    Expression,
+    /// A glob, which is more specific the more of it is not a wildcard.
+    Glob(usize),
+    Exact,
+}
+
+impl ModifierId {
+    pub fn as_str(&self) -> &str {
+        self.id.get_ref()
+    }
+
+    pub fn is_match(&self, id: &str) -> bool {
+        self.pattern.is_match(id)
+    }
+
+    /// Whether a test id names a test. A test with vectors is also named by its test function, so a modifier for
+    /// `base16_encoding` applies to `base16_encoding[0]` and every other vector.
+    pub fn is_test_match(&self, test_name: &str) -> bool {
+        self.is_match(test_name) || self.is_match(function_name(test_name))
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        self.specificity != Specificity::Exact
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        self.specificity
+    }
+}
+
+impl<'de> Deserialize<'de> for ModifierId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = Spanned::<String>::deserialize(deserializer)?;
+        let pattern = Pattern::whole(id.get_ref()).map_err(serde::de::Error::custom)?;
+        let text = id.get_ref();
+        let specificity = if text.starts_with("re:") {
+            Specificity::Expression
+        } else if text.contains(['*', '?']) {
+            Specificity::Glob(text.chars().filter(|c| !matches!(c, '*' | '?')).count())
+        } else {
+            Specificity::Exact
+        };
+
+        Ok(ModifierId {
+            id,
+            pattern,
+            specificity,
+        })
+    }
+}
+
+/// What a modifier does to a test or an assertion, other than changing its assertion type.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModifierAction {
+    /// The requirement no longer applies, such as when a later specification removes it. A disabled test is not
+    /// run and a disabled assertion has no result, both are reported as withdrawn.
+    Disable,
+}
+
+impl SpecModifiers {
+    /// The ids of the tests and assertions which are disabled without saying why.
+    pub fn missing_reasons(&self) -> Vec<&Spanned<String>> {
+        let mut missing = Vec::new();
+        for test_modifier in self.spec_modifiers.iter().flat_map(|sm| &sm.test_modifiers) {
+            if test_modifier.action.is_some() && test_modifier.reason.is_none() {
+                missing.push(&test_modifier.test_id.id);
+            }
+
+            for assertion_modifier in &test_modifier.assertion_modifiers {
+                if assertion_modifier.action.is_some() && assertion_modifier.reason.is_none() {
+                    missing.push(&assertion_modifier.assertion_id.id);
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// A modifier file, which keeps its source so that a modifier can be traced back to where it was written.
+#[derive(Debug)]
+pub struct ModifierFile {
+    pub path: String,
+    source: String,
+    pub modifiers: SpecModifiers,
+}
+
+impl ModifierFile {
+    /// The file and line of an id in the file.
+    pub fn location<T>(&self, spanned: &Spanned<T>) -> String {
+        format!("{}:{}", self.path, line_of(&self.source, spanned.span().start))
+    }
 }
 
-pub fn load_spec_modifier(spec_modifier_path: &str) -> Result<SpecModifiers, CognoError> {
+pub fn load_spec_modifier(spec_modifier_path: &str) -> Result<ModifierFile, CognoError> {
     let mut f = File::open(spec_modifier_path)?;
 
     let mut buf = String::new();
     f.read_to_string(&mut buf)?;
 
-    toml::from_str(&buf).map_err(|e| e.into())
+    let spec_modifiers: SpecModifiers = toml::from_str(&buf)?;
+    if let Some(id) = spec_modifiers.missing_reasons().first() {
+        return Err(CognoError::MissingModifierReason(id.get_ref().clone()));
+    }
+
+    Ok(ModifierFile {
+        path: spec_modifier_path.to_string(),
+        source: buf,
+        modifiers: spec_modifiers,
+    })
+}
+
+/// The modifier files passed with `--modifier`, in the order they were given.
+pub fn modifier_paths() -> Vec<String> {
+    std::env::var("COGNO_MODIFIERS")
+        .unwrap_or("".to_string())
+        .split(",")
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect()
+}
+
+/// Whether later modifier files override earlier ones, set with `cargo cogno --layer-modifiers`. Otherwise
+/// modifiers from different files which change an assertion in different ways conflict.
+pub fn is_layered() -> bool {
+    "true"
+        == std::env::var("COGNO_LAYER_MODIFIERS")
+            .unwrap_or(String::from("false"))
+            .as_str()
+}
+
+/// The most specific of the modifiers which match an assertion of a test, with what the caller matched it from. The
+/// assertion id is compared first and then the test id. Equally specific modifiers conflict unless they change the
+/// assertion in the same way.
+pub fn most_specific<T: Copy>(
+    matched: &[(T, &TestModifier, &AssertionModifier)],
+) -> Result<Option<T>, CognoError> {
+    let precedence = |(_, tm, am): &(T, &TestModifier, &AssertionModifier)| {
+        (am.assertion_id.specificity(), tm.test_id.specificity())
+    };
+    let highest = matched.iter().map(precedence).max();
+    let most_specific: Vec<&(T, &TestModifier, &AssertionModifier)> = matched
+        .iter()
+        .filter(|m| Some(precedence(m)) == highest)
+        .collect();
+
+    let mut distinct: Vec<&AssertionModifier> = Vec::new();
+    for (_, _, am) in &most_specific {
+        if !distinct.iter().any(|d| d.has_same_effect(am)) {
+            distinct.push(am);
+        }
+    }
+    if distinct.len() > 1 {
+        return Err(CognoError::ConflictingModifiers(format!("{:?}", distinct)));
+    }
+
+    Ok(most_specific.first().map(|(matched, _, _)| *matched))
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> SpecModifiers {
+        toml::from_str(source).unwrap()
+    }
+
+    fn id(text: &str) -> ModifierId {
+        #[derive(Deserialize)]
+        struct Id {
+            id: ModifierId,
+        }
+        toml::from_str::<Id>(&format!("id = {:?}", text)).unwrap().id
+    }
+
+    /// Every assertion modifier in the file, labelled with its index, in the form given to `most_specific`.
+    fn matched(modifiers: &SpecModifiers) -> Vec<(usize, &TestModifier, &AssertionModifier)> {
+        modifiers
+            .spec_modifiers
+            .iter()
+            .flat_map(|sm| &sm.test_modifiers)
+            .flat_map(|tm| tm.assertion_modifiers.iter().map(move |am| (tm, am)))
+            .enumerate()
+            .map(|(index, (tm, am))| (index, tm, am))
+            .collect()
+    }
+
+    #[test]
+    fn specificity_of_ids() {
+        assert_eq!(Specificity::Exact, id("rfc_1234_sec_8.2").specificity());
+        assert_eq!(Specificity::Glob(13), id("rfc_1234_sec_*").specificity());
+        assert_eq!(Specificity::Glob(15), id("rfc_1234_sec_8.?").specificity());
+        assert_eq!(Specificity::Expression, id("re:rfc_1234_sec_8\\..*").specificity());
+    }
+
+    #[test]
+    fn exact_is_more_specific_than_glob_and_glob_than_expression() {
+        assert!(Specificity::Exact > Specificity::Glob(100));
+        assert!(Specificity::Glob(2) > Specificity::Glob(1));
+        assert!(Specificity::Glob(0) > Specificity::Expression);
+    }
+
+    #[test]
+    fn most_specific_assertion_id_applies() {
+        let modifiers = parse(
+            r#"
+            [[spec_modifiers]]
+            spec_id = "1234"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "re:rfc_1234_.*", assertion_type = "May" },
+                { assertion_id = "rfc_*", assertion_type = "May" },
+                { assertion_id = "rfc_1234_sec_8.*", assertion_type = "Should" },
+                { assertion_id = "rfc_1234_sec_8.2", assertion_type = "MustNot" },
+              ] },
+            ]
+            "#,
+        );
+
+        assert_eq!(Some(3), most_specific(&matched(&modifiers)).unwrap());
+        assert_eq!(Some(2), most_specific(&matched(&modifiers)[..3]).unwrap());
+        assert_eq!(Some(1), most_specific(&matched(&modifiers)[..2]).unwrap());
+    }
+
+    #[test]
+    fn assertion_id_is_compared_before_test_id() {
+        let modifiers = parse(
+            r#"
+            [[spec_modifiers]]
+            spec_id = "1234"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_sec_8.*", assertion_type = "May" },
+              ] },
+              { test_id = "*", assertion_modifiers = [
+                { assertion_id = "rfc_1234_sec_8.2", assertion_type = "Should" },
+              ] },
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_sec_8.2", assertion_type = "MustNot" },
+              ] },
+            ]
+            "#,
+        );
+
+        assert_eq!(Some(1), most_specific(&matched(&modifiers)[..2]).unwrap());
+        assert_eq!(Some(2), most_specific(&matched(&modifiers)).unwrap());
+    }
+
+    #[test]
+    fn equally_specific_modifiers_with_different_effects_conflict() {
+        let modifiers = parse(
+            r#"
+            [[spec_modifiers]]
+            spec_id = "1234"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_sec_8.2", assertion_type = "Should" },
+              ] },
+            ]
+
+            [[spec_modifiers]]
+            spec_id = "1235"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_sec_8.2", assertion_type = "May" },
+              ] },
+            ]
+            "#,
+        );
+
+        assert!(matches!(
+            most_specific(&matched(&modifiers)),
+            Err(CognoError::ConflictingModifiers(_))
+        ));
+    }
+
+    #[test]
+    fn equally_specific_modifiers_with_the_same_effect_do_not_conflict() {
+        let modifiers = parse(
+            r#"
+            [[spec_modifiers]]
+            spec_id = "1234"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_*", assertion_type = "Should", expect_fail = "see #42" },
+                { assertion_id = "*_sec_8.2", assertion_type = "Should", expect_fail = "see #42" },
+              ] },
+            ]
+
+            [[spec_modifiers]]
+            spec_id = "1235"
+            test_modifiers = [
+              { test_id = "test", assertion_modifiers = [
+                { assertion_id = "rfc_1234_*", assertion_type = "Should", expect_fail = "see #42" },
+              ] },
+            ]
+            "#,
+        );
+
+        assert_eq!(Some(0), most_specific(&matched(&modifiers)).unwrap());
+    }
+
+    #[test]
+    fn no_matching_modifiers() {
+        assert_eq!(None, most_specific::<usize>(&[]).unwrap());
+    }
 }
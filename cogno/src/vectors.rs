@@ -0,0 +1,75 @@
+use crate::error::CognoError;
+use serde::de::value::SeqDeserializer;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+struct VectorFile<T> {
+    vectors: Vec<T>,
+}
+
+/// Used by the test harness. Not for direct use.
+///
+/// Parses the content of a test vector file into one value per vector. The format is chosen by the
+/// extension of the path
+///
+/// - `json` is an array of vectors.
+/// - `toml` is an array of tables named `vectors`, written as `[[vectors]]`.
+/// - `hex` has one hex encoded vector per line, decoded to bytes. Blank lines and lines starting
+///   with `#` are ignored.
+pub fn parse_vectors<T: DeserializeOwned>(path: &str, content: &str) -> Result<Vec<T>, CognoError> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+
+    match extension {
+        "json" => serde_json::from_str(content).map_err(|e| vector_error(path, e)),
+        "toml" => toml::from_str::<VectorFile<T>>(content)
+            .map(|f| f.vectors)
+            .map_err(|e| vector_error(path, e)),
+        "hex" => parse_hex_vectors(content).map_err(|e| vector_error(path, e)),
+        _ => Err(vector_error(
+            path,
+            "unsupported file type, expected json, toml or hex",
+        )),
+    }
+}
+
+fn parse_hex_vectors<T: DeserializeOwned>(content: &str) -> Result<Vec<T>, String> {
+    let mut vectors = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line: String = line.split_whitespace().collect();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let bytes = decode_hex(&line).map_err(|e| format!("line {} - {}", line_number + 1, e))?;
+        let deserializer: SeqDeserializer<_, serde::de::value::Error> =
+            SeqDeserializer::new(bytes.into_iter());
+        let vector = T::deserialize(deserializer)
+            .map_err(|e| format!("line {} - {}", line_number + 1, e))?;
+        vectors.push(vector);
+    }
+
+    Ok(vectors)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    hex.as_bytes()
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|p| p.len() == 2)
+                .and_then(|p| u8::from_str_radix(p, 16).ok())
+                .ok_or_else(|| format!("invalid hex at position {}", i * 2))
+        })
+        .collect()
+}
+
+fn vector_error<E: ToString>(path: &str, e: E) -> CognoError {
+    CognoError::VectorFormatError(format!("{} - {}", path, e.to_string()))
+}
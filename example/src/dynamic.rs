@@ -0,0 +1,11 @@
+use cogno::TestFactory;
+
+#[cogno_test_factory]
+pub fn palindromes(factory: &mut TestFactory) {
+    for word in ["level", "rotor", "kayak"] {
+        factory.register(&format!("palindrome_{}", word), &["1235"], move || {
+            let reversed: String = word.chars().rev().collect();
+            should_eq!("palindrome", word, reversed.as_str());
+        });
+    }
+}
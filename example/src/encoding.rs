@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct Base16Vector {
+    input: String,
+    encoded: String,
+}
+
+#[cogno_test(spec = "4648", vectors = "vectors/rfc4648.toml")]
+pub fn base16_encoding(vector: Base16Vector) {
+    let encoded: String = vector
+        .input
+        .bytes()
+        .map(|b| format!("{:02X}", b))
+        .collect();
+
+    must_eq!("rfc_4648_sec_8", vector.encoded, encoded);
+}
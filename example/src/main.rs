@@ -3,6 +3,8 @@ extern crate cogno;
 
 use std::process::Command;
 
+mod dynamic;
+mod encoding;
 mod other;
 
 #[cogno_test(spec = "1234")]
@@ -16,15 +18,24 @@ fn panic_test() {
     panic!("I'm not a great test");
 }
 
+#[cogno_test(spec = "1234")]
+fn returns_error() -> Result<(), std::num::ParseIntError> {
+    let port: u16 = "not a port".parse()?;
+
+    must_eq!("rfc_1234_port", 53, port);
+    Ok(())
+}
+
 #[cogno_test]
 fn always_test() {
     should_eq!("always_assert_me", "a", "b");
 }
 
-#[cogno_test(spec = "1234")]
+#[cogno_test(spec = "1234", tags = ["process"])]
 fn failed_assertions() {
     let child = Command::new("ls").arg("-al").spawn().unwrap();
     let mut close_handle = defer_close!(child);
+    cogno::tracing::info!(command = "ls", "started the command");
 
     must_eq!("rfc_must", 'a', 'b');
     must_not_eq!("rfc_must_not", 'a', 'a');
@@ -1,4 +1,137 @@
+use cogno::ContextKey;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cogno_test(spec = "1235", depends_on = ["tester"])]
+pub fn builds_on_tester() {
+    should_eq!("rfc_1235_builds_on_tester", 'a', 'a');
+}
+
 #[cogno_test(spec = "1235")]
 pub fn tester() {
     should_eq!("tester", "a", "a");
 }
+
+#[cogno_test(spec = ["1234", "1235"])]
+pub fn restated_requirement() {
+    must_eq!("rfc_1234_sec_9", 1, 1);
+}
+
+#[cogno_test(spec = "1235")]
+pub fn unsupported_feature() {
+    let supports_feature = false;
+    if !supports_feature {
+        skip!("the feature is optional and not supported");
+    }
+
+    must_eq!("rfc_1235_optional_feature", 1, 1);
+}
+
+#[cogno_test(spec = "1235")]
+pub fn environment_dependent() {
+    let port_available = false;
+    require!(port_available, "port 5353 is in use");
+
+    must_eq!("rfc_1235_listens", 1, 1);
+}
+
+#[cogno_test(spec = "1235", depends_on = ["environment_dependent"])]
+pub fn needs_environment() {
+    must_eq!("rfc_1235_uses_listener", 1, 1);
+}
+
+const SESSION_ID: ContextKey<u32> = ContextKey::new("session_id");
+
+#[cogno_test(spec = "1235")]
+pub fn open_session() {
+    let session_id = 42;
+    context_set!(SESSION_ID, session_id);
+
+    must_not_eq!("rfc_1235_session_id_assigned", 0, session_id);
+}
+
+#[cogno_test(spec = "1235", depends_on = ["open_session"])]
+pub fn resume_session() {
+    let session_id = context_get!(SESSION_ID);
+
+    must_eq!("rfc_1235_session_resumes", Some(42), session_id);
+}
+
+#[cogno_test(spec = "1235")]
+pub fn session_lifecycle() {
+    let version = step!("negotiate", {
+        must_eq!("rfc_1235_version_offered", 2, 2);
+        2
+    });
+
+    step!("request", {
+        for method in ["GET", "HEAD"] {
+            subtest!(method, {
+                should_eq!("rfc_1235_method_supported", version, 2);
+            });
+        }
+    });
+
+    step!("close", {
+        must_eq!("rfc_1235_close_acknowledged", true, true);
+    });
+}
+
+#[cogno_test(spec = "1235")]
+pub fn parses_port() -> Result<(), std::num::ParseIntError> {
+    let port: u16 = "5353".parse()?;
+
+    must_eq!("rfc_1235_port", 5353, port);
+    Ok(())
+}
+
+#[cogno_test(spec = "1235", expect_fail = "truncated responses are not retried over TCP yet")]
+pub fn retries_truncated_response() {
+    let retried = false;
+
+    must_eq!("rfc_1235_tcp_retry", true, retried);
+}
+
+static CONNECTION_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+#[cogno_test(spec = "1235", retries = 2)]
+pub fn unreliable_connection() {
+    // Fails the first time to show a flaky test
+    let connected = CONNECTION_ATTEMPTS.fetch_add(1, Ordering::SeqCst) > 0;
+
+    must_eq!("rfc_1235_connects", true, connected);
+}
+
+static LISTENER_BOUND: AtomicBool = AtomicBool::new(false);
+
+/// Stands in for binding a fixed port, which only one test can do at a time.
+fn with_listener(f: impl FnOnce()) -> bool {
+    let already_bound = LISTENER_BOUND.swap(true, Ordering::SeqCst);
+    f();
+    LISTENER_BOUND.store(false, Ordering::SeqCst);
+    !already_bound
+}
+
+#[cogno_test(spec = "1235", serial = "port-5353")]
+pub fn accepts_udp() {
+    let bound = with_listener(|| std::thread::sleep(std::time::Duration::from_millis(20)));
+
+    must_eq!("rfc_1235_udp_listener", true, bound);
+}
+
+#[cogno_test(spec = "1235", serial = "port-5353")]
+pub fn accepts_tcp() {
+    let bound = with_listener(|| std::thread::sleep(std::time::Duration::from_millis(20)));
+
+    must_eq!("rfc_1235_tcp_listener", true, bound);
+}
+
+#[cogno_test(spec = "1235")]
+pub fn concurrent_queries() {
+    std::thread::scope(|scope| {
+        for query in ["a.example", "b.example"] {
+            cogno::spawn_scoped(scope, move || {
+                must_eq!("rfc_1235_concurrent_query", true, query.ends_with(".example"));
+            });
+        }
+    });
+}